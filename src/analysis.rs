@@ -0,0 +1,482 @@
+use std::collections::HashMap;
+
+/// A round value that occurs suspiciously more often than the integers around it.
+#[derive(Debug, PartialEq)]
+pub struct Heap {
+    pub value: i64,
+    pub count: usize,
+    pub neighbor_mean: f64,
+}
+
+/// Returns how over-represented multiples of `base` are among the integer `values`.
+///
+/// Without heaping one in every `base` integers is a multiple of it, so the index is about 1.0;
+/// values well above 1.0 indicate rounding to multiples of `base`. Returns `None` when none of
+/// the values are integers.
+pub fn heaping_index(values: &[f64], base: u32) -> Option<f64> {
+    let integers: Vec<i64> = integer_values(values).collect();
+    if integers.is_empty() {
+        return None;
+    }
+
+    let multiples = integers.iter().filter(|&&v| v % base as i64 == 0).count();
+
+    Some(multiples as f64 / integers.len() as f64 * base as f64)
+}
+
+/// Returns the multiples of `base` whose count is at least `ratio` times the mean count of the
+/// two integers either side of them, most heaped first.
+pub fn heaped_values(values: &[f64], base: u32, ratio: f64) -> Vec<Heap> {
+    let mut counts: HashMap<i64, usize> = HashMap::new();
+    for v in integer_values(values) {
+        *counts.entry(v).or_default() += 1;
+    }
+
+    let mut heaps: Vec<Heap> = counts
+        .iter()
+        .filter(|&(&value, _)| value % base as i64 == 0)
+        .filter_map(|(&value, &count)| {
+            let neighbor_mean = [-2, -1, 1, 2]
+                .iter()
+                .map(|offset| counts.get(&(value + offset)).copied().unwrap_or(0))
+                .sum::<usize>() as f64
+                / 4.0;
+
+            (count as f64 >= ratio * neighbor_mean.max(1.0)).then_some(Heap {
+                value,
+                count,
+                neighbor_mean,
+            })
+        })
+        .collect();
+
+    heaps.sort_by(|a, b| {
+        let excess = |heap: &Heap| heap.count as f64 / heap.neighbor_mean.max(1.0);
+        excess(b).total_cmp(&excess(a)).then(a.value.cmp(&b.value))
+    });
+    heaps
+}
+
+/// The first significant digit counts of `values`, indexed by digit minus one.
+///
+/// Zero and non-finite values have no significant digit and are not counted.
+pub fn first_digits(values: &[f64]) -> [usize; 9] {
+    let mut counts = [0; 9];
+    for &v in values {
+        if let Some(digit) = first_digit(v) {
+            counts[digit as usize - 1] += 1;
+        }
+    }
+
+    counts
+}
+
+/// The fraction of first significant digits Benford's law expects for each digit, in order.
+pub fn benford_expected() -> [f64; 9] {
+    std::array::from_fn(|i| (1.0 + 1.0 / (i as f64 + 1.0)).log10())
+}
+
+/// Pearson's chi-square statistic for first digit `counts` against Benford's law, along with
+/// its p-value on the eight degrees of freedom.
+pub fn benford_chi_square(counts: &[usize; 9]) -> (f64, f64) {
+    let total = counts.iter().sum::<usize>() as f64;
+    let chi_square = counts
+        .iter()
+        .zip(benford_expected())
+        .map(|(&observed, p)| {
+            let expected = total * p;
+            (observed as f64 - expected).powi(2) / expected
+        })
+        .sum();
+
+    (chi_square, chi_square_sf(chi_square, 8))
+}
+
+/// The two-sample Kolmogorov–Smirnov statistic `D` of `a` and `b`, along with its asymptotic
+/// p-value under the hypothesis that both were drawn from the same distribution.
+///
+/// NaN values are left out. Returns `None` if either sample has no other values.
+pub fn ks_two_sample(a: &[f64], b: &[f64]) -> Option<(f64, f64)> {
+    let (a, b) = (sorted_numbers(a), sorted_numbers(b));
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    let (n, m) = (a.len() as f64, b.len() as f64);
+    let (mut i, mut j, mut d) = (0, 0, 0.0f64);
+    while i < a.len() && j < b.len() {
+        let x = a[i].min(b[j]);
+        while i < a.len() && a[i] <= x {
+            i += 1;
+        }
+        while j < b.len() && b[j] <= x {
+            j += 1;
+        }
+        d = d.max((i as f64 / n - j as f64 / m).abs());
+    }
+
+    let effective = (n * m / (n + m)).sqrt();
+    let lambda = (effective + 0.12 + 0.11 / effective) * d;
+    Some((d, kolmogorov_sf(lambda)))
+}
+
+/// `points` pairs of matching quantiles of `a` and `b` for a Q–Q plot, each with its
+/// probability, at probabilities evenly spaced strictly between zero and one.
+///
+/// Quantiles interpolate linearly between the sorted values, leaving out NaN. Returns no pairs
+/// if either sample has no other values.
+pub fn qq_pairs(a: &[f64], b: &[f64], points: usize) -> Vec<(f64, f64, f64)> {
+    let (a, b) = (sorted_numbers(a), sorted_numbers(b));
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+
+    (0..points)
+        .map(|i| {
+            let p = (i as f64 + 0.5) / points as f64;
+            (p, sorted_quantile(&a, p), sorted_quantile(&b, p))
+        })
+        .collect()
+}
+
+/// The `p` quantile of `values`, interpolating linearly between the sorted values, or `None`
+/// if there are none.
+pub fn quantile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    Some(sorted_quantile(&sorted, p))
+}
+
+/// The `p` quantile of `(value, weight)` pairs, where each value holds its share of the total
+/// weight.
+///
+/// Each value sits at the middle of its share of the cumulative weight, and quantiles
+/// interpolate linearly between them, so only the weights' proportions matter and weights
+/// summing to 1 give the same quantiles as the counts they stand for. Values without a
+/// positive weight are left out, and `None` is returned if none are left.
+pub fn weighted_quantile(values: &[(f64, f64)], p: f64) -> Option<f64> {
+    let mut sorted: Vec<(f64, f64)> = values
+        .iter()
+        .copied()
+        .filter(|&(value, weight)| !value.is_nan() && weight > 0.0)
+        .collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    // The fraction of the total weight below the middle of each value's share of it.
+    let total: f64 = sorted.iter().map(|&(_, weight)| weight).sum();
+    let positions: Vec<f64> = sorted
+        .iter()
+        .scan(0.0, |below, &(_, weight)| {
+            let position = (*below + weight / 2.0) / total;
+            *below += weight;
+            Some(position)
+        })
+        .collect();
+
+    let i = positions.partition_point(|&position| position < p);
+    if i == 0 {
+        return Some(sorted[0].0);
+    }
+    if i == sorted.len() {
+        return Some(sorted[i - 1].0);
+    }
+    let (lower, upper) = (sorted[i - 1].0, sorted[i].0);
+    let fraction = (p - positions[i - 1]) / (positions[i] - positions[i - 1]);
+    Some(lower + (upper - lower) * fraction)
+}
+
+/// The values that are not NaN, in ascending order.
+fn sorted_numbers(values: &[f64]) -> Vec<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    sorted.sort_by(f64::total_cmp);
+    sorted
+}
+
+/// The `p` quantile of the non-empty ascending `sorted`, interpolating between neighbors.
+fn sorted_quantile(sorted: &[f64], p: f64) -> f64 {
+    let rank = p * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
+
+/// The Kolmogorov distribution's survival function `2 * sum((-1)^(j-1) * exp(-2 j^2 x^2))`.
+fn kolmogorov_sf(x: f64) -> f64 {
+    if x < 1e-3 {
+        return 1.0;
+    }
+
+    let sum: f64 = (1..=100)
+        .map(|j| {
+            let sign = if j % 2 == 1 { 1.0 } else { -1.0 };
+            sign * (-2.0 * (j * j) as f64 * x * x).exp()
+        })
+        .sum();
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// The Anderson–Darling normality statistic of `values`, adjusted for the mean and variance
+/// being estimated from them, along with its approximate p-value.
+///
+/// Returns `None` for fewer than eight values or values without spread.
+pub fn anderson_darling(values: &[f64]) -> Option<(f64, f64)> {
+    let n = values.len();
+    if n < 8 {
+        return None;
+    }
+
+    let mean = values.iter().sum::<f64>() / n as f64;
+    let sd = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64).sqrt();
+    if sd == 0.0 || !sd.is_finite() {
+        return None;
+    }
+
+    let mut z: Vec<f64> = values.iter().map(|v| (v - mean) / sd).collect();
+    z.sort_by(f64::total_cmp);
+
+    let sum: f64 = (0..n)
+        .map(|i| {
+            let lower = normal_cdf(z[i]).ln();
+            let upper = normal_cdf(-z[n - 1 - i]).ln();
+            (2 * i + 1) as f64 * (lower + upper)
+        })
+        .sum();
+    let n = n as f64;
+    let a2 = -n - sum / n;
+    let a2 = a2 * (1.0 + 0.75 / n + 2.25 / (n * n));
+
+    // D'Agostino & Stephens (1986), table 4.9.
+    let p_value = if a2 >= 0.6 {
+        (1.2937 - 5.709 * a2 + 0.0186 * a2 * a2).exp()
+    } else if a2 >= 0.34 {
+        (0.9177 - 4.279 * a2 - 1.38 * a2 * a2).exp()
+    } else if a2 >= 0.2 {
+        1.0 - (-8.318 + 42.796 * a2 - 59.938 * a2 * a2).exp()
+    } else {
+        1.0 - (-13.436 + 101.14 * a2 - 223.73 * a2 * a2).exp()
+    };
+
+    Some((a2, p_value.clamp(0.0, 1.0)))
+}
+
+/// The probability that a normal variable with this `mean` and `stddev` falls between `lower`
+/// and `upper`, all of it on the mean's side when `stddev` is 0.
+pub fn normal_mass(lower: f64, upper: f64, mean: f64, stddev: f64) -> f64 {
+    if stddev == 0.0 {
+        return if (lower..upper).contains(&mean) {
+            1.0
+        } else {
+            0.0
+        };
+    }
+    normal_cdf((upper - mean) / stddev) - normal_cdf((lower - mean) / stddev)
+}
+
+/// The standard normal CDF, by way of the complementary error function.
+fn normal_cdf(z: f64) -> f64 {
+    0.5 * erfc(-z / std::f64::consts::SQRT_2)
+}
+
+/// The complementary error function, to a fractional error below 1.2e-7 everywhere.
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.5 * x.abs());
+    let poly = [
+        -1.265_512_23,
+        1.000_023_68,
+        0.374_091_96,
+        0.096_784_18,
+        -0.186_288_06,
+        0.278_868_07,
+        -1.135_203_98,
+        1.488_515_87,
+        -0.822_152_23,
+        0.170_872_77,
+    ]
+    .iter()
+    .rev()
+    .fold(0.0, |acc, c| acc * t + c);
+    let result = t * (-x * x + poly).exp();
+
+    if x >= 0.0 { result } else { 2.0 - result }
+}
+
+fn first_digit(v: f64) -> Option<u8> {
+    let v = v.abs();
+    if !v.is_normal() {
+        return None;
+    }
+
+    let scaled = v / 10f64.powf(v.log10().floor());
+    // Rounding in log10 can land just either side of [1, 10).
+    Some((scaled.floor() as u8).clamp(1, 9))
+}
+
+/// The chi-square survival function for an even number of degrees of freedom, which has the
+/// closed form `exp(-x/2) * sum((x/2)^k / k!)` for `k < dof / 2`.
+fn chi_square_sf(x: f64, dof: u32) -> f64 {
+    debug_assert!(dof.is_multiple_of(2));
+    let half = x / 2.0;
+    let (sum, _) = (0..dof / 2).fold((0.0, 1.0), |(sum, term), k| {
+        let term = if k == 0 { 1.0 } else { term * half / k as f64 };
+        (sum + term, term)
+    });
+
+    (-half).exp() * sum
+}
+
+fn integer_values(values: &[f64]) -> impl Iterator<Item = i64> + '_ {
+    values
+        .iter()
+        .filter(|v| v.is_finite() && v.fract() == 0.0)
+        .map(|&v| v as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heaping_index_of_uniform_integers() {
+        let values: Vec<f64> = (0..100).map(|v| v as f64).collect();
+        assert_eq!(heaping_index(&values, 10), Some(1.0));
+        assert_eq!(heaping_index(&[0.5, 1.5], 10), None);
+    }
+
+    #[test]
+    fn first_digits_skip_zero() {
+        let values = vec![1.0, 0.019, -250.0, 9.99, 0.0, f64::NAN, 1000.0];
+        assert_eq!(first_digits(&values), [3, 1, 0, 0, 0, 0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn benford_chi_square_of_ideal_counts() {
+        let counts = [301, 176, 125, 97, 79, 67, 58, 51, 46];
+        let (chi_square, p_value) = benford_chi_square(&counts);
+        assert!(chi_square < 0.1);
+        assert!(p_value > 0.99);
+    }
+
+    #[test]
+    fn chi_square_sf_matches_tables() {
+        // The 5% critical value for eight degrees of freedom.
+        assert!((chi_square_sf(15.507, 8) - 0.05).abs() < 1e-4);
+    }
+
+    #[test]
+    fn ks_two_sample_of_identical_samples() {
+        let a: Vec<f64> = (0..50).map(|v| v as f64).collect();
+        let (d, p_value) = ks_two_sample(&a, &a).unwrap();
+        assert_eq!(d, 0.0);
+        assert_eq!(p_value, 1.0);
+    }
+
+    #[test]
+    fn ks_two_sample_of_shifted_samples() {
+        let a: Vec<f64> = (0..100).map(|v| v as f64).collect();
+        let b: Vec<f64> = a.iter().map(|v| v + 50.0).collect();
+        let (d, p_value) = ks_two_sample(&a, &b).unwrap();
+        assert_eq!(d, 0.5);
+        assert!(p_value < 1e-9);
+        assert_eq!(ks_two_sample(&a, &[]), None);
+    }
+
+    #[test]
+    fn ks_two_sample_leaves_out_nan() {
+        let (d, _) = ks_two_sample(&[1.0, f64::NAN, 3.0], &[2.0, f64::NAN, 5.0]).unwrap();
+        assert_eq!(d, 0.5);
+        assert_eq!(ks_two_sample(&[f64::NAN], &[1.0]), None);
+        assert_eq!(
+            qq_pairs(&[1.0, f64::NAN, 3.0], &[f64::NAN, 2.0, 4.0], 1),
+            vec![(0.5, 2.0, 3.0)]
+        );
+    }
+
+    #[test]
+    fn qq_pairs_of_shifted_samples() {
+        let a: Vec<f64> = (0..=10).map(|v| v as f64).collect();
+        let b: Vec<f64> = a.iter().rev().map(|v| v * 2.0 + 1.0).collect();
+        assert_eq!(
+            qq_pairs(&a, &b, 2),
+            vec![(0.25, 2.5, 6.0), (0.75, 7.5, 16.0)]
+        );
+        assert_eq!(qq_pairs(&a, &[], 2), vec![]);
+    }
+
+    #[test]
+    fn anderson_darling_accepts_normal_quantiles() {
+        // Evenly spaced quantiles of a standard normal, via the logistic approximation.
+        let values: Vec<f64> = (1..=200)
+            .map(|i| {
+                let p = i as f64 / 201.0;
+                (p / (1.0 - p)).ln() / 1.702
+            })
+            .collect();
+        let (_, p_value) = anderson_darling(&values).unwrap();
+        assert!(p_value > 0.05);
+    }
+
+    #[test]
+    fn anderson_darling_rejects_exponential() {
+        let values: Vec<f64> = (1..=200).map(|i| -(1.0 - i as f64 / 201.0).ln()).collect();
+        let (a2, p_value) = anderson_darling(&values).unwrap();
+        assert!(a2 > 1.0);
+        assert!(p_value < 0.01);
+        assert_eq!(anderson_darling(&[1.0; 10]), None);
+    }
+
+    #[test]
+    fn erfc_matches_tables() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-7);
+        assert!((erfc(1.0) - 0.157_299_207).abs() < 1e-7);
+        assert!((erfc(-1.0) - 1.842_700_793).abs() < 1e-7);
+    }
+
+    #[test]
+    fn weighted_quantiles_ignore_the_scale() {
+        let weighted = [(10.0, 2.0), (1.0, 3.0), (4.0, 1.0), (7.0, 0.0)];
+        let scaled = weighted.map(|(value, weight)| (value, weight / 8.0));
+        for p in [0.0, 0.3, 0.5, 0.75, 0.9, 1.0] {
+            assert_eq!(
+                weighted_quantile(&weighted, p),
+                weighted_quantile(&scaled, p)
+            );
+        }
+        assert_eq!(weighted_quantile(&weighted, 0.0), Some(1.0));
+        assert_eq!(weighted_quantile(&weighted, 0.25), Some(1.0));
+        assert_eq!(weighted_quantile(&weighted, 1.0), Some(10.0));
+        assert_eq!(
+            weighted_quantile(&[(2.0, 0.25), (6.0, 0.25)], 0.5),
+            Some(4.0)
+        );
+        assert_eq!(weighted_quantile(&[(2.0, 0.0)], 0.5), None);
+    }
+
+    #[test]
+    fn normal_mass_covers_the_bell() {
+        assert!((normal_mass(-1.0, 1.0, 0.0, 1.0) - 0.682_689_49).abs() < 1e-6);
+        assert!((normal_mass(10.0, 14.0, 12.0, 2.0) - 0.682_689_49).abs() < 1e-6);
+        assert_eq!(normal_mass(0.0, 1.0, 0.5, 0.0), 1.0);
+        assert_eq!(normal_mass(1.0, 2.0, 0.5, 0.0), 0.0);
+    }
+
+    #[test]
+    fn heaped_values_find_round_spikes() {
+        let mut values: Vec<f64> = (0..30).map(|v| v as f64).collect();
+        values.extend(std::iter::repeat_n(20.0, 9));
+
+        assert_eq!(
+            heaped_values(&values, 10, 3.0),
+            vec![Heap {
+                value: 20,
+                count: 10,
+                neighbor_mean: 1.0
+            }]
+        );
+    }
+}
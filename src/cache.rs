@@ -0,0 +1,132 @@
+//! A small least recently used cache, for skipping repeated work on repeated input.
+
+use std::collections::HashMap;
+
+const NONE: usize = usize::MAX;
+
+/// Maps up to `capacity` string keys to values, forgetting the least recently used key to
+/// make room for a new one.
+#[derive(Debug, Clone)]
+pub struct LruCache<V> {
+    capacity: usize,
+    slots: HashMap<String, usize>,
+    entries: Vec<Entry<V>>,
+    /// The most recently used entry.
+    head: usize,
+    /// The least recently used entry.
+    tail: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<V> {
+    key: String,
+    value: V,
+    prev: usize,
+    next: usize,
+}
+
+impl<V> LruCache<V> {
+    /// An empty cache holding at most `capacity` keys, which must be at least one.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "an LRU cache needs room for a key");
+        Self {
+            capacity,
+            slots: HashMap::with_capacity(capacity),
+            entries: Vec::with_capacity(capacity),
+            head: NONE,
+            tail: NONE,
+        }
+    }
+
+    /// The value of `key`, marking it most recently used.
+    pub fn get(&mut self, key: &str) -> Option<&V> {
+        let i = *self.slots.get(key)?;
+        self.unlink(i);
+        self.push_front(i);
+        Some(&self.entries[i].value)
+    }
+
+    /// Stores `value` under `key`, forgetting the least recently used key if the cache is full.
+    pub fn insert(&mut self, key: String, value: V) {
+        if let Some(&i) = self.slots.get(&key) {
+            self.entries[i].value = value;
+            self.unlink(i);
+            self.push_front(i);
+            return;
+        }
+
+        let i = if self.entries.len() < self.capacity {
+            self.entries.push(Entry {
+                key: key.clone(),
+                value,
+                prev: NONE,
+                next: NONE,
+            });
+            self.entries.len() - 1
+        } else {
+            let i = self.tail;
+            self.unlink(i);
+            let entry = &mut self.entries[i];
+            self.slots.remove(&entry.key);
+            entry.key.clone_from(&key);
+            entry.value = value;
+            i
+        };
+        self.slots.insert(key, i);
+        self.push_front(i);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let (prev, next) = (self.entries[i].prev, self.entries[i].next);
+        match prev {
+            NONE => self.head = next,
+            prev => self.entries[prev].next = next,
+        }
+        match next {
+            NONE => self.tail = prev,
+            next => self.entries[next].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, i: usize) {
+        self.entries[i].prev = NONE;
+        self.entries[i].next = self.head;
+        match self.head {
+            NONE => self.tail = i,
+            head => self.entries[head].prev = i,
+        }
+        self.head = i;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forgets_least_recently_used() {
+        let mut cache = LruCache::new(2);
+        cache.insert("a".to_string(), 1.0);
+        cache.insert("b".to_string(), 2.0);
+        assert_eq!(cache.get("a"), Some(&1.0));
+
+        cache.insert("c".to_string(), 3.0);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(&1.0));
+        assert_eq!(cache.get("c"), Some(&3.0));
+
+        cache.insert("a".to_string(), 4.0);
+        cache.insert("d".to_string(), 5.0);
+        assert_eq!(cache.get("c"), None);
+        assert_eq!(cache.get("a"), Some(&4.0));
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -0,0 +1,137 @@
+//! Gaussian kernel density estimates, a smooth alternative to binning whose shape does not
+//! hinge on the number of bins.
+
+use crate::{Error, Sample, Stats, analysis};
+use std::f64::consts::PI;
+
+/// How many bandwidths past the extreme samples [`Kde::grid`] reaches, where the density has
+/// all but vanished.
+const GRID_REACH: f64 = 3.0;
+
+/// A Gaussian kernel of width `bandwidth` centred on each sample.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Kde {
+    samples: Vec<f64>,
+    bandwidth: f64,
+}
+
+impl Kde {
+    /// An estimate over the finite `values`, with its bandwidth picked by Silverman's rule of
+    /// thumb. Fails as [`crate::Histogram::from_values`] does when the values have no spread.
+    pub fn new<T: Sample>(values: &[T]) -> Result<Self, Error> {
+        let samples = finite(values)?;
+        let stats = Stats::from_values(&samples).ok_or(Error::NoValues)?;
+        let iqr = analysis::quantile(&samples, 0.75).unwrap_or(0.0)
+            - analysis::quantile(&samples, 0.25).unwrap_or(0.0);
+        // A spread of zero means no usable bandwidth, so fall back to whichever measure has one.
+        let spread = match (stats.stddev, iqr / 1.34) {
+            (stddev, iqr) if stddev > 0.0 && iqr > 0.0 => stddev.min(iqr),
+            (stddev, iqr) => stddev.max(iqr),
+        };
+        if spread == 0.0 {
+            return Err(match samples.len() {
+                1 => Error::SingleValue(samples[0]),
+                _ => Error::IdenticalValues(samples[0]),
+            });
+        }
+
+        let bandwidth = 0.9 * spread * (samples.len() as f64).powf(-0.2);
+        Ok(Self { samples, bandwidth })
+    }
+
+    /// An estimate over the finite `values` with the given `bandwidth`, the standard deviation
+    /// of each sample's kernel.
+    pub fn with_bandwidth<T: Sample>(values: &[T], bandwidth: f64) -> Result<Self, Error> {
+        if !(bandwidth.is_finite() && bandwidth > 0.0) {
+            return Err(Error::InvalidBandwidth(bandwidth));
+        }
+
+        Ok(Self {
+            samples: finite(values)?,
+            bandwidth,
+        })
+    }
+
+    pub fn bandwidth(&self) -> f64 {
+        self.bandwidth
+    }
+
+    /// The estimated probability density at `x`.
+    pub fn density(&self, x: f64) -> f64 {
+        let norm = self.samples.len() as f64 * self.bandwidth * (2.0 * PI).sqrt();
+        let sum: f64 = self
+            .samples
+            .iter()
+            .map(|sample| (-0.5 * ((x - sample) / self.bandwidth).powi(2)).exp())
+            .sum();
+        sum / norm
+    }
+
+    /// The density at `points` evenly spaced `x`s reaching [`GRID_REACH`] bandwidths past the
+    /// smallest and largest samples, as `(x, density)` pairs.
+    pub fn grid(&self, points: usize) -> Vec<(f64, f64)> {
+        let (min, max) = self
+            .samples
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &sample| {
+                (min.min(sample), max.max(sample))
+            });
+        let (lower, upper) = (
+            min - GRID_REACH * self.bandwidth,
+            max + GRID_REACH * self.bandwidth,
+        );
+        let step = (upper - lower) / points.saturating_sub(1).max(1) as f64;
+
+        (0..points)
+            .map(|i| lower + i as f64 * step)
+            .map(|x| (x, self.density(x)))
+            .collect()
+    }
+}
+
+/// The finite `values`, failing when there are none.
+fn finite<T: Sample>(values: &[T]) -> Result<Vec<f64>, Error> {
+    let samples: Vec<f64> = values
+        .iter()
+        .map(|value| value.to_f64())
+        .filter(|value| value.is_finite())
+        .collect();
+    if samples.is_empty() {
+        return Err(Error::NoValues);
+    }
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn density_integrates_to_one() {
+        let kde = Kde::new(&[1.0, 2.0, 2.5, 3.0, 7.0, f64::NAN]).unwrap();
+        let grid = kde.grid(2001);
+        let step = grid[1].0 - grid[0].0;
+        let area: f64 = grid.iter().map(|(_, density)| density * step).sum();
+        assert!((area - 1.0).abs() < 1e-2, "area {}", area);
+
+        let (peak, _) = grid
+            .iter()
+            .copied()
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .unwrap();
+        assert!((1.5..3.5).contains(&peak), "peak {}", peak);
+    }
+
+    #[test]
+    fn single_kernels_are_normal() {
+        let kde = Kde::with_bandwidth(&[0.0], 2.0).unwrap();
+        let normal = 1.0 / (2.0 * (2.0 * PI).sqrt());
+        assert!((kde.density(0.0) - normal).abs() < 1e-12);
+        assert_eq!(Kde::new(&[4.0, 4.0]), Err(Error::IdenticalValues(4.0)));
+        assert_eq!(
+            Kde::with_bandwidth(&[1.0], -1.0),
+            Err(Error::InvalidBandwidth(-1.0))
+        );
+    }
+}
@@ -1,13 +1,138 @@
-use std::{collections::HashSet, str::FromStr};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+pub mod analysis;
+pub mod cache;
+pub mod kde;
+pub mod pipeline;
+pub mod rhst;
+pub mod schema;
+pub mod sniff;
+pub mod tdigest;
 
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Error {
     MissingColumn(String, u32),
     FailedParse(String, String),
+    MalformedRhst(usize, String),
+    MalformedSchema(usize, String),
+    UnsupportedRhstVersion(u32),
+    InvalidEdges(Vec<f64>),
+    MismatchedEdges(Vec<f64>, Vec<f64>),
+    /// The index and value of a NaN or infinite value refused by [`NonFinitePolicy::Error`].
+    NonFinite(usize, f64),
+    /// There were no values, other than NaN, to bin.
+    NoValues,
+    /// There was only this one value, which leaves the bins no width.
+    SingleValue(f64),
+    /// Every value was this one, which leaves the bins no width.
+    IdenticalValues(f64),
+    /// Zero bins were asked for.
+    NoBins,
+    /// Bins were asked to span from the first bound to the second, which are not finite, or
+    /// not in ascending order.
+    InvalidRange(f64, f64),
+    /// A bin width that is not positive and finite.
+    InvalidWidth(f64),
+    /// A [`kde::Kde`] bandwidth that is not positive and finite.
+    InvalidBandwidth(f64),
+    /// A [`tdigest::TDigest`] compression that is not positive and at most
+    /// [`tdigest::MAX_COMPRESSION`].
+    InvalidCompression(f64),
+    /// Binning parameters, e.g. a [`Binning`]'s or a [`Histogram::rebin`] factor, that cannot
+    /// make edges, for the given reason.
+    InvalidBinning(String),
+    /// What went wrong with the zero indexed row of a [`pipeline::Pipeline`]'s input.
+    Row(usize, Box<Error>),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::MissingColumn(row, column) => write!(f, "`{}` has no column {}", row, column),
+            Error::FailedParse(text, kind) => write!(f, "`{}` is not a {}", text, kind),
+            Error::MalformedRhst(line, reason) => write!(f, "line {}: {}", line, reason),
+            Error::MalformedSchema(line, reason) => write!(f, "line {}: {}", line, reason),
+            Error::UnsupportedRhstVersion(version) => {
+                write!(
+                    f,
+                    "rhst version {} is newer than {}",
+                    version,
+                    rhst::VERSION
+                )
+            }
+            Error::InvalidEdges(edges) => {
+                write!(
+                    f,
+                    "edges {:?} do not ascend through two or more values",
+                    edges
+                )
+            }
+            Error::MismatchedEdges(a, b) => match (span(a), span(b)) {
+                (a, b) if a == b => write!(f, "the histograms split {} differently", a),
+                (a, b) => write!(f, "the histograms have different bins, {} and {}", a, b),
+            },
+            Error::NonFinite(i, value) => write!(f, "value {} is {}", i, value),
+            Error::NoValues => write!(f, "there are no values"),
+            Error::SingleValue(value) => {
+                write!(
+                    f,
+                    "there is only one value, {}, so the bins have no width",
+                    value
+                )
+            }
+            Error::IdenticalValues(value) => {
+                write!(f, "every value is {}, so the bins have no width", value)
+            }
+            Error::NoBins => write!(f, "at least one bin is needed"),
+            Error::InvalidRange(lower, upper) => {
+                write!(
+                    f,
+                    "range {} to {} does not ascend between finite bounds",
+                    lower, upper
+                )
+            }
+            Error::InvalidWidth(width) => write!(f, "bin width {} is not positive", width),
+            Error::InvalidBandwidth(bandwidth) => {
+                write!(f, "bandwidth {} is not positive", bandwidth)
+            }
+            Error::InvalidCompression(compression) => write!(
+                f,
+                "compression {} is not positive and at most {}",
+                compression,
+                tdigest::MAX_COMPRESSION
+            ),
+            Error::InvalidBinning(reason) => write!(f, "{}", reason),
+            Error::Row(i, err) => write!(f, "row {}: {}", i, err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A short description of the bins between `edges`, e.g. `4 bins from 0 to 10`, for errors
+/// about edges too long to write out.
+fn span(edges: &[f64]) -> String {
+    match edges {
+        [first, last] => format!("1 bin from {} to {}", first, last),
+        [first, .., last] => format!("{} bins from {} to {}", edges.len() - 1, first, last),
+        _ => "no bins".to_string(),
+    }
 }
 
+/// The length of the longest bar in a histogram's [`fmt::Display`] chart.
+const DISPLAY_WIDTH: usize = 40;
+
+/// How many bins a [`HistogramBuilder`] needs before it counts into a map of only the bins
+/// values land in, as most of that many bins are likely to stay empty.
+pub const SPARSE_BINS: usize = 1 << 16;
+
+/// The most bins an [`Exponential`] binning splits values into, as a wide span at many
+/// significant digits can need more edges than fit in memory.
+pub const MAX_EXPONENTIAL_BINS: usize = 1 << 20;
+
 pub struct ColumnParser<T> {
-    columns: HashSet<u32>,
+    columns: Vec<u32>,
     delim: String,
     _phantom: std::marker::PhantomData<T>,
 }
@@ -15,7 +140,7 @@ pub struct ColumnParser<T> {
 impl<T: FromStr> ColumnParser<T> {
     pub fn new(columns: &[u32], delim: &str) -> Self {
         Self {
-            columns: HashSet::from_iter(columns.iter().cloned()),
+            columns: columns.to_vec(),
             delim: delim.to_string(),
             _phantom: std::marker::PhantomData,
         }
@@ -42,128 +167,2494 @@ impl<T: FromStr> ColumnParser<T> {
 
         Ok(result)
     }
+
+    /// Parses the columns of each of `rows` in turn onto the end of `values`, which saves the
+    /// allocation [`ColumnParser::parse_row`] makes for every row.
+    ///
+    /// Stops at the first row that fails to parse, returning its index along with why, with
+    /// the values of the rows before it already in `values`.
+    pub fn parse_rows_batch<S: AsRef<str>>(
+        &self,
+        rows: &[S],
+        values: &mut Vec<T>,
+    ) -> Result<(), (usize, Error)> {
+        values.reserve(rows.len() * self.columns.len());
+        for (i, row) in rows.iter().enumerate() {
+            let row = row.as_ref();
+            let start = values.len();
+            let parsed = match self.columns[..] {
+                [column] => {
+                    let field = row.split(&self.delim).nth(column as usize);
+                    self.parse_field(row, field, column)
+                        .map(|value| values.push(value))
+                }
+                _ => {
+                    let fields: Vec<&str> = row.split(&self.delim).collect();
+                    self.columns.iter().try_for_each(|&column| {
+                        let field = fields.get(column as usize).copied();
+                        values.push(self.parse_field(row, field, column)?);
+                        Ok(())
+                    })
+                }
+            };
+            if let Err(err) = parsed {
+                values.truncate(start);
+                return Err((i, err));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_field(&self, row: &str, field: Option<&str>, column: u32) -> Result<T, Error> {
+        let field = field.ok_or_else(|| Error::MissingColumn(row.to_string(), column))?;
+        field.parse::<T>().map_err(|_| {
+            Error::FailedParse(field.to_string(), std::any::type_name::<T>().to_string())
+        })
+    }
 }
 
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bin {
+    /// The bin's midpoint, or a value standing for the bin such as its one distinct value.
     pub label: f64,
-    pub count: usize,
+    pub lower: f64,
+    pub upper: f64,
+    /// The number of values in the bin, or the sum of their weights if weighted.
+    pub count: f64,
+}
+
+/// A smoothing kernel for [`Histogram::smooth`], with widths measured in bins.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kernel {
+    /// A Gaussian with the given standard deviation, truncated at three of them.
+    Gaussian(f64),
+    /// An unweighted mean over the given number of bins either side.
+    Boxcar(usize),
+}
+
+impl Kernel {
+    fn weights(&self) -> Vec<f64> {
+        match *self {
+            Kernel::Gaussian(sigma) => {
+                let radius = (3.0 * sigma).ceil() as i64;
+                (-radius..=radius)
+                    .map(|offset| (-0.5 * (offset as f64 / sigma).powi(2)).exp())
+                    .collect()
+            }
+            Kernel::Boxcar(radius) => vec![1.0; 2 * radius + 1],
+        }
+    }
+}
+
+/// A rule of thumb for choosing the number of bins from the values themselves.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutoBins {
+    /// `log2(n) + 1` bins, which assumes roughly normal values and underfits large samples.
+    Sturges,
+    /// Bins `3.49 * stddev / cbrt(n)` wide, for roughly normal values.
+    Scott,
+    /// Bins `2 * IQR / cbrt(n)` wide, which outliers affect less than [`AutoBins::Scott`].
+    FreedmanDiaconis,
+    /// `2 * cbrt(n)` bins.
+    Rice,
+}
+
+impl AutoBins {
+    /// The number of bins the rule picks for `values`, ignoring NaN, and at least one.
+    ///
+    /// Width based rules fall back to [`AutoBins::Sturges`] when the values have no spread.
+    pub fn num_bins<T: Sample>(&self, values: &[T]) -> usize {
+        let numbers = || {
+            values
+                .iter()
+                .map(|value| value.to_f64())
+                .filter(|value| !value.is_nan())
+        };
+        let count = numbers().count();
+        let n = count as f64;
+        if count < 2 {
+            return 1;
+        }
+
+        let sturges = (n.log2().ceil() as usize + 1).max(1);
+        let (min, max) = numbers().fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        let range = max - min;
+        let width = match self {
+            AutoBins::Sturges => return sturges,
+            AutoBins::Rice => return ((2.0 * n.cbrt()).ceil() as usize).max(1),
+            AutoBins::Scott => {
+                let mean = numbers().sum::<f64>() / n;
+                let variance = numbers().map(|v| (v - mean).powi(2)).sum::<f64>() / n;
+                3.49 * variance.sqrt() / n.cbrt()
+            }
+            AutoBins::FreedmanDiaconis => {
+                // Only the quartiles need the values in order, so only this rule sorts a copy.
+                let mut sorted: Vec<f64> = numbers().collect();
+                sorted.sort_by(f64::total_cmp);
+                let quartile = |q: f64| {
+                    let rank = q * (n - 1.0);
+                    let (below, above) =
+                        (sorted[rank.floor() as usize], sorted[rank.ceil() as usize]);
+                    below + (above - below) * rank.fract()
+                };
+                2.0 * (quartile(0.75) - quartile(0.25)) / n.cbrt()
+            }
+        };
+
+        if width > 0.0 && range.is_finite() {
+            ((range / width).ceil() as usize).max(1)
+        } else {
+            sturges
+        }
+    }
+}
+
+/// A way of picking bin edges from the values they will count, for [`Histogram::binned`].
+pub trait Binning {
+    /// The strictly ascending edges to count `values` between.
+    fn edges<T: Sample>(&self, values: &[T]) -> Result<Vec<f64>, Error>;
+}
+
+/// Even bins spanning the values, as many as the rule picks.
+impl Binning for AutoBins {
+    fn edges<T: Sample>(&self, values: &[T]) -> Result<Vec<f64>, Error> {
+        let (min, max) = spread(values)?;
+        let builder = HistogramBuilder::new(min, max, self.num_bins(values))?;
+        Ok(builder.finish().edges().to_vec())
+    }
+}
+
+/// HdrHistogram style buckets: each power of `base` up to the values' largest is split into
+/// even sub-buckets narrow enough to tell values apart to `significant_digits`, so resolution
+/// is fine near zero and coarse in the tail.
+///
+/// Edges start at the power of `base` at or below the smallest positive value, or at zero if
+/// there are zeros. Negative values fall below them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Exponential {
+    pub base: f64,
+    pub significant_digits: u32,
+}
+
+impl Default for Exponential {
+    fn default() -> Self {
+        Self {
+            base: 2.0,
+            significant_digits: 2,
+        }
+    }
+}
+
+impl Binning for Exponential {
+    fn edges<T: Sample>(&self, values: &[T]) -> Result<Vec<f64>, Error> {
+        if !(self.base.is_finite() && self.base > 1.0) {
+            return Err(Error::InvalidBinning(format!(
+                "base {} is not above 1",
+                self.base
+            )));
+        }
+        if !(1..=6).contains(&self.significant_digits) {
+            return Err(Error::InvalidBinning(format!(
+                "{} significant digits is not from 1 to 6",
+                self.significant_digits
+            )));
+        }
+
+        let counted = values
+            .iter()
+            .map(|value| value.to_f64())
+            .filter(|value| value.is_finite() && *value >= 0.0);
+        let (smallest, max, zeros) = counted.fold(
+            (f64::INFINITY, f64::NEG_INFINITY, false),
+            |(smallest, max, zeros), value| match value {
+                0.0 => (smallest, max.max(value), true),
+                _ => (smallest.min(value), max.max(value), zeros),
+            },
+        );
+        if max == 0.0 {
+            return Err(Error::IdenticalValues(0.0));
+        }
+        if !max.is_finite() {
+            return Err(Error::NoValues);
+        }
+
+        let sub_buckets = ((self.base - 1.0) * 10f64.powi(self.significant_digits as i32)).ceil();
+        // Below the smallest normal float the powers of the base lose precision and then
+        // underflow to zero, so subnormal values share the lowest normal bucket.
+        let lowest = smallest.max(f64::MIN_POSITIVE);
+        let mut magnitude = lowest.log(self.base).floor() as i32;
+        // Rounding in `log` can land one power of the base above the value.
+        if self.base.powi(magnitude) > lowest {
+            magnitude -= 1;
+        }
+        let powers = max.log(self.base).floor() as i32 - magnitude + 1;
+        let bins = powers as f64 * sub_buckets;
+        if bins > MAX_EXPONENTIAL_BINS as f64 {
+            return Err(Error::InvalidBinning(format!(
+                "{:e} to {:e} at {} significant digits needs about {} bins, more than {}",
+                smallest, max, self.significant_digits, bins, MAX_EXPONENTIAL_BINS
+            )));
+        }
+
+        let mut edges = if zeros { vec![0.0] } else { Vec::new() };
+        loop {
+            let lower = self.base.powi(magnitude);
+            let step = lower * (self.base - 1.0) / sub_buckets;
+            for i in 0..sub_buckets as usize {
+                let edge = lower + i as f64 * step;
+                // The last bin needs to reach past the largest value, just as the first bin
+                // holds the smallest.
+                if edges.len() >= 2 && edges[edges.len() - 1] >= max {
+                    return Ok(edges);
+                }
+                edges.push(edge);
+            }
+            magnitude += 1;
+        }
+    }
 }
 
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Histogram {
     bins: Vec<Bin>,
+    edges: Vec<f64>,
+    stats: Option<Stats>,
+    underflow: f64,
+    overflow: f64,
+}
+
+/// The smallest and largest of `values` other than NaN, failing as [`Histogram::from_values`]
+/// does when they leave bins between them no width.
+fn spread<T: Sample>(values: &[T]) -> Result<(f64, f64), Error> {
+    let (count, min, max) = values
+        .iter()
+        .map(|value| value.to_f64())
+        .filter(|value| !value.is_nan())
+        .fold(
+            (0, f64::INFINITY, f64::NEG_INFINITY),
+            |(count, min, max), value| (count + 1, min.min(value), max.max(value)),
+        );
+    match count {
+        0 => Err(Error::NoValues),
+        1 => Err(Error::SingleValue(min)),
+        _ if min == max => Err(Error::IdenticalValues(min)),
+        _ => Ok((min, max)),
+    }
 }
 
 impl Histogram {
-    pub fn from_values(values: Vec<f64>, num_bins: usize) -> Self {
-        let bins = match values
+    /// Counts `values` into `num_bins` bins evenly spanning the smallest of them to the largest.
+    ///
+    /// Fails without bins to count into, or when the values leave them no width: when there are
+    /// none, or only one, or they are all the same. NaN is never counted.
+    pub fn from_values<T: Sample>(values: Vec<T>, num_bins: usize) -> Result<Self, Error> {
+        if num_bins == 0 {
+            return Err(Error::NoBins);
+        }
+        let (min, max) = spread(&values)?;
+        let mut builder = HistogramBuilder::new(min, max, num_bins)?;
+        values.into_iter().for_each(|value| builder.push(value));
+        Ok(builder.finish())
+    }
+
+    /// Bins `values` evenly like [`Histogram::from_values`], spanning only the finite values and
+    /// handling NaN and infinities as `policy` says.
+    pub fn from_values_with<T: Sample>(
+        values: Vec<T>,
+        num_bins: usize,
+        policy: NonFinitePolicy,
+    ) -> Result<Self, Error> {
+        let finite = values
+            .iter()
+            .map(|value| value.to_f64())
+            .filter(|value| value.is_finite());
+        let range = finite.fold(None, |acc: Option<(f64, f64)>, value| match acc {
+            Some((min, max)) => Some((min.min(value), max.max(value))),
+            None => Some((value, value)),
+        });
+        let (lower, upper) = range.ok_or(Error::NoValues)?;
+
+        // Clamped infinities land on the finite range, so it spans every value counted.
+        let counted = |each: &mut dyn FnMut(f64)| {
+            for (i, value) in values.iter().enumerate() {
+                if let Some(value) = policy.apply_one(i, value.to_f64(), lower, upper)? {
+                    each(value);
+                }
+            }
+            Ok::<(), Error>(())
+        };
+        if num_bins == 0 || lower == upper {
+            let mut count = 0;
+            counted(&mut |_| count += 1)?;
+            return Err(match count {
+                _ if num_bins == 0 => Error::NoBins,
+                1 => Error::SingleValue(lower),
+                _ => Error::IdenticalValues(lower),
+            });
+        }
+        let mut builder = HistogramBuilder::new(lower, upper, num_bins)?;
+        counted(&mut |value| builder.push(value))?;
+        Ok(builder.finish())
+    }
+
+    /// Bins `values` evenly like [`Histogram::from_values`], with as many bins as `rule` picks.
+    pub fn auto<T: Sample>(values: Vec<T>, rule: AutoBins) -> Result<Self, Error> {
+        let num_bins = rule.num_bins(&values);
+        Histogram::from_values(values, num_bins)
+    }
+
+    /// Bins `(value, weight)` pairs like [`Histogram::from_values`], each value adding its
+    /// weight to its bin rather than one. Pairs with a NaN value or weight are not counted.
+    pub fn from_weighted_values<T: Sample>(
+        values: &[(T, f64)],
+        num_bins: usize,
+    ) -> Result<Self, Error> {
+        let pairs: Vec<(f64, f64)> = values
+            .iter()
+            .map(|&(value, weight)| (value.to_f64(), weight))
+            .filter(|(value, weight)| !value.is_nan() && !weight.is_nan())
+            .collect();
+        let range = pairs
             .iter()
-            .fold(None, |acc: Option<(f64, f64)>, &value| match acc {
+            .fold(None, |acc: Option<(f64, f64)>, &(value, _)| match acc {
                 Some((min, max)) => Some((min.min(value), max.max(value))),
-                None => Some((f64::INFINITY, f64::NEG_INFINITY)),
-            }) {
+                None => Some((value, value)),
+            });
+
+        match range {
+            _ if num_bins == 0 => Err(Error::NoBins),
+            None => Err(Error::NoValues),
+            Some((min, max)) if min == max => match pairs.len() {
+                1 => Err(Error::SingleValue(min)),
+                _ => Err(Error::IdenticalValues(min)),
+            },
             Some((min, max)) => {
-                let bin_width = (max - min) / num_bins as f64;
-                let mut bins: Vec<Bin> = (0..num_bins)
-                    .into_iter()
-                    .map(|i| i as f64 * bin_width + min + bin_width / 2.0)
-                    .map(|label| Bin { label, count: 0 })
-                    .collect();
+                let mut builder = HistogramBuilder::new(min, max, num_bins)?;
+                for (value, weight) in pairs {
+                    builder.push_weighted(value, weight);
+                }
+                Ok(builder.finish())
+            }
+        }
+    }
 
-                values
-                    .iter()
-                    .map(|&value| {
-                        // FIXME: Some kind of race condition here?
-                        ((value - min) / (max.next_up() - min) * num_bins as f64).floor() as usize
-                    })
-                    .for_each(|i| bins[i].count += 1);
+    /// Counts each distinct value exactly, in a bin of its own labelled with the value.
+    ///
+    /// Edges fall halfway between neighboring values, so consecutive integers get bins one
+    /// wide centred on them. NaN is not counted.
+    pub fn discrete<T: Sample>(values: &[T]) -> Self {
+        let mut sorted: Vec<f64> = values
+            .iter()
+            .map(|value| value.to_f64())
+            .filter(|value| !value.is_nan())
+            .collect();
+        sorted.sort_by(f64::total_cmp);
 
-                bins
+        let stats = Stats::from_values(&sorted);
+        let mut bins: Vec<Bin> = Vec::new();
+        for value in sorted {
+            match bins.last_mut() {
+                Some(bin) if bin.label == value => bin.count += 1.0,
+                _ => bins.push(Bin {
+                    label: value,
+                    lower: value,
+                    upper: value,
+                    count: 1.0,
+                }),
             }
-            None => Vec::new(),
-        };
+        }
+
+        let labels: Vec<f64> = bins.iter().map(|bin| bin.label).collect();
+        let mut edges: Vec<f64> = labels.windows(2).map(|w| (w[0] + w[1]) / 2.0).collect();
+        if let (Some(&first), Some(&last)) = (labels.first(), labels.last()) {
+            // The ends extend as far past the extreme values as the nearest inner edge.
+            let first_half = edges.first().map_or(0.5, |&edge| edge - first);
+            let last_half = edges.last().map_or(0.5, |&edge| last - edge);
+            edges.insert(0, first - first_half);
+            edges.push(last + last_half);
+        }
+        for (bin, edge) in bins.iter_mut().zip(edges.windows(2)) {
+            (bin.lower, bin.upper) = (edge[0], edge[1]);
+        }
 
-        Histogram { bins }
+        Histogram {
+            bins,
+            edges,
+            stats,
+            ..Histogram::default()
+        }
     }
 
-    pub fn into_bins(self) -> Vec<Bin> {
-        self.bins
+    /// Counts the positive `values` into `num_bins` bins whose widths grow geometrically from
+    /// the smallest of them to the largest, labelled with their geometric midpoints.
+    ///
+    /// For values spanning orders of magnitude, where even bins crowd into the first one. Zero,
+    /// negative and non-finite values are not counted, and the positive ones fail as
+    /// [`Histogram::from_values`] does when they leave the bins no width.
+    pub fn log_spaced<T: Sample>(values: &[T], num_bins: usize) -> Result<Self, Error> {
+        let positive: Vec<f64> = values
+            .iter()
+            .map(|value| value.to_f64())
+            .filter(|value| value.is_finite() && *value > 0.0)
+            .collect();
+        let min = positive.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = positive.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+        if positive.is_empty() || min == max || num_bins == 0 {
+            return Histogram::from_values(positive, num_bins);
+        }
+
+        let step = (max.ln() - min.ln()) / num_bins as f64;
+        let mut edges: Vec<f64> = (0..=num_bins)
+            .map(|i| (min.ln() + i as f64 * step).exp())
+            .collect();
+        // Pin the ends so rounding in `exp` cannot leave the extremes outside.
+        edges[0] = min;
+        edges[num_bins] = max;
+
+        let mut histo = Histogram::with_edges(&positive, &edges).expect("log edges ascend");
+        for (bin, edge) in histo.bins.iter_mut().zip(edges.windows(2)) {
+            bin.label = (edge[0] * edge[1]).sqrt();
+        }
+
+        Ok(histo)
     }
 
-    pub fn into_counts(self) -> Vec<usize> {
-        self.bins.into_iter().map(|bin| bin.count).collect()
+    /// Counts `values` into `num_bins` even bins spanning `lower` to `upper` rather than the
+    /// range of `values`, so histograms of different inputs share a layout. Fails as
+    /// [`HistogramBuilder::new`] does.
+    pub fn with_range<T: Sample>(
+        values: &[T],
+        num_bins: usize,
+        lower: f64,
+        upper: f64,
+        out_of_range: OutOfRange,
+    ) -> Result<Self, Error> {
+        let mut builder = HistogramBuilder::new(lower, upper, num_bins)?.out_of_range(out_of_range);
+        values.iter().for_each(|&value| builder.push(value));
+        Ok(builder.finish())
     }
 
-    pub fn into_labels(self) -> Vec<f64> {
-        self.bins.into_iter().map(|bin| bin.label).collect()
+    /// Counts `values` into the bins between `edges`, which need not be evenly spaced.
+    ///
+    /// Values outside `edges` are not counted in any bin, only in [`Histogram::underflow`] or
+    /// [`Histogram::overflow`]. Errors unless `edges` has at least two values in strictly
+    /// ascending order.
+    pub fn with_edges<T: Sample>(values: &[T], edges: &[f64]) -> Result<Self, Error> {
+        if !valid_edges(edges) {
+            return Err(Error::InvalidEdges(edges.to_vec()));
+        }
+
+        let mut histo = Histogram::from_parts(edges.to_vec(), vec![0.0; edges.len() - 1]);
+        let mut moments = Moments::default();
+        for value in values {
+            let value = value.to_f64();
+            match histo.bin_index(value) {
+                Some(i) => {
+                    histo.bins[i].count += 1.0;
+                    moments.push(value, 1.0);
+                }
+                None if value < edges[0] => histo.underflow += 1.0,
+                None if value > edges[edges.len() - 1] => histo.overflow += 1.0,
+                None => {}
+            }
+        }
+        histo.stats = moments.finish();
+
+        Ok(histo)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Counts `values` into the bins between the edges `binning` picks for them, as
+    /// [`Histogram::with_edges`] does.
+    pub fn binned<T: Sample>(values: &[T], binning: &impl Binning) -> Result<Self, Error> {
+        Histogram::with_edges(values, &binning.edges(values)?)
+    }
 
-    #[test]
-    fn parse_column_from_rows() {
-        let parser = ColumnParser::<f64>::single(1, ",");
-        let result: Vec<f64> = vec!["1.0,2.0,3.0", "4.0,5.0,6.0", "7.0,8.0,9.0"]
-            .into_iter()
-            .map(|row| parser.parse_row(&row).unwrap()[0])
+    /// Counts `values` into bins `width` wide with edges at whole multiples of `width` from
+    /// `origin`, as many as it takes to cover the finite values, e.g. width 5 from 0 for bins
+    /// 0 to 5, 5 to 10 and so on.
+    ///
+    /// Round widths read more easily than the range split `num_bins` ways. Infinities are
+    /// counted only in [`Histogram::underflow`] or [`Histogram::overflow`] and NaN not at all.
+    /// Errors unless `width` is positive and finite and there is a finite value.
+    pub fn with_bin_width<T: Sample>(values: &[T], width: f64, origin: f64) -> Result<Self, Error> {
+        if !(width.is_finite() && width > 0.0) {
+            return Err(Error::InvalidWidth(width));
+        }
+        let (min, max) = values
+            .iter()
+            .map(|value| value.to_f64())
+            .filter(|value| value.is_finite())
+            .fold(None, |acc: Option<(f64, f64)>, value| match acc {
+                Some((min, max)) => Some((min.min(value), max.max(value))),
+                None => Some((value, value)),
+            })
+            .ok_or(Error::NoValues)?;
+
+        // Edges are counted in widths from the origin so they land on exact multiples, and
+        // widened by one where rounding would leave an extreme value outside them.
+        let edge = |i: f64| origin + i * width;
+        let mut first = ((min - origin) / width).floor();
+        if edge(first) > min {
+            first -= 1.0;
+        }
+        let mut last = ((max - origin) / width).ceil().max(first + 1.0);
+        if edge(last) < max {
+            last += 1.0;
+        }
+        let edges: Vec<f64> = (0..=(last - first) as usize)
+            .map(|i| edge(first + i as f64))
             .collect();
 
-        assert_eq!(result, vec![2.0, 5.0, 8.0]);
+        Histogram::with_edges(values, &edges)
     }
 
-    #[test]
-    fn missing_column() {
-        let parser = ColumnParser::<f64>::new(&[1], ",");
-        let result: Vec<Result<_, Error>> = vec!["1.0,2.0,3.0", "4.0", "7.0,8.0,9.0"]
+    /// Builds a histogram from bin `edges` and the `counts` between them, labelling each bin
+    /// with its midpoint. Without the values there are no [`Stats`].
+    fn from_parts(edges: Vec<f64>, counts: Vec<f64>) -> Self {
+        let bins = counts
             .into_iter()
-            .map(|row| parser.parse_row(&row))
+            .zip(edges.windows(2))
+            .map(|(count, edge)| Bin {
+                label: (edge[0] + edge[1]) / 2.0,
+                lower: edge[0],
+                upper: edge[1],
+                count,
+            })
             .collect();
 
-        assert_eq!(
-            result,
-            vec![
-                Ok(vec![2.0]),
-                Err(Error::MissingColumn("4.0".to_string(), 1)),
-                Ok(vec![8.0])
-            ]
-        );
+        Histogram {
+            bins,
+            edges,
+            ..Histogram::default()
+        }
     }
 
-    #[test]
-    fn failed_parse() {
-        let parser = ColumnParser::<f64>::new(&[1], ",");
-        let result: Vec<Result<_, Error>> =
-            vec!["1.0,2.0,3.0", "4.0,not_a_float,6.0", "7.0,8.0,9.0"]
-                .into_iter()
-                .map(|row| parser.parse_row(&row))
-                .collect();
+    /// Redistributes the counts onto new bin `edges`, splitting each bin in proportion to how
+    /// much of it overlaps each new bin.
+    ///
+    /// Split counts keep their fractional shares, so weighted and normalized counts keep all of
+    /// the total landing inside `edges`. Counts outside `edges` are dropped, and the result has
+    /// no [`Stats`]. Errors unless `edges` has at least two values in strictly ascending order.
+    pub fn rebin_to(&self, edges: &[f64]) -> Result<Histogram, Error> {
+        if !valid_edges(edges) {
+            return Err(Error::InvalidEdges(edges.to_vec()));
+        }
 
-        assert_eq!(
-            result,
-            vec![
-                Ok(vec![2.0]),
-                Err(Error::FailedParse(
-                    "not_a_float".to_string(),
-                    "f64".to_string()
-                )),
-                Ok(vec![8.0])
-            ]
-        );
+        let mut counts = vec![0.0; edges.len() - 1];
+        for (bin, old) in self.bins.iter().zip(self.edges.windows(2)) {
+            let (lower, upper) = (old[0], old[1]);
+            for (count, new) in counts.iter_mut().zip(edges.windows(2)) {
+                let fraction = if upper > lower {
+                    (upper.min(new[1]) - lower.max(new[0])).max(0.0) / (upper - lower)
+                } else if new[0] <= lower && lower <= new[1] {
+                    1.0
+                } else {
+                    0.0
+                };
+                *count += fraction * bin.count;
+            }
+        }
+
+        Ok(Histogram::from_parts(edges.to_vec(), counts))
     }
 
-    #[test]
-    fn histogram_counts_from_values() {
-        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
-        let histogram = Histogram::from_values(values, 3);
-        assert_eq!(histogram.into_counts(), vec![5, 3, 2]);
+    /// Merges each run of `factor` adjacent bins into one, with the last bin taking whatever
+    /// bins are left over.
+    ///
+    /// No count is split, unlike with [`Histogram::rebin_to`], so the statistics and the counts
+    /// outside the edges are kept. Errors if `factor` is zero.
+    pub fn rebin(&self, factor: usize) -> Result<Histogram, Error> {
+        if factor == 0 {
+            return Err(Error::InvalidBinning(
+                "cannot rebin by a factor of zero".to_string(),
+            ));
+        }
+        let counts = self
+            .bins
+            .chunks(factor)
+            .map(|bins| bins.iter().map(|bin| bin.count).sum())
+            .collect();
+        let mut edges: Vec<f64> = self.edges.iter().step_by(factor).copied().collect();
+        if !self.bins.len().is_multiple_of(factor) {
+            edges.extend(self.edges.last());
+        }
+
+        Ok(Histogram {
+            stats: self.stats.clone(),
+            underflow: self.underflow,
+            overflow: self.overflow,
+            ..Histogram::from_parts(edges, counts)
+        })
     }
 
-    #[test]
-    fn histogram_labels_from_values() {
-        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
-        let histogram = Histogram::from_values(values, 3);
-        assert_eq!(histogram.into_labels(), vec![0.5, 1.5, 2.5]);
+    /// Flags bins whose count deviates from the mean of the `radius` bins either side of it
+    /// by more than `k` standard deviations, treating counts as Poisson distributed.
+    pub fn anomalies(&self, radius: usize, k: f64) -> Vec<bool> {
+        (0..self.bins.len())
+            .map(|i| {
+                let neighbors: Vec<f64> = (i.saturating_sub(radius)..(i + radius + 1))
+                    .filter(|&j| j != i)
+                    .filter_map(|j| self.bins.get(j))
+                    .map(|bin| bin.count)
+                    .collect();
+                if neighbors.is_empty() {
+                    return false;
+                }
+
+                let baseline = neighbors.iter().sum::<f64>() / neighbors.len() as f64;
+                let sigma = baseline.max(1.0).sqrt();
+                (self.bins[i].count - baseline).abs() > k * sigma
+            })
+            .collect()
+    }
+
+    /// Returns each count as a fraction of the total count.
+    pub fn normalized(&self) -> Vec<f64> {
+        let total = self.bins.iter().map(|bin| bin.count).sum::<f64>();
+        self.bins.iter().map(|bin| bin.count / total).collect()
+    }
+
+    /// Returns each count divided by the width of its bin.
+    pub fn density(&self) -> Vec<f64> {
+        self.bins
+            .iter()
+            .zip(self.edges.windows(2))
+            .map(|(bin, edge)| bin.count / (edge[1] - edge[0]))
+            .collect()
+    }
+
+    /// Returns the counts convolved with `kernel`.
+    ///
+    /// Near the ends the kernel is renormalized over the bins that exist, so smoothing neither
+    /// loses nor invents counts at the edges.
+    pub fn smooth(&self, kernel: Kernel) -> Vec<f64> {
+        let weights = kernel.weights();
+        let radius = weights.len() / 2;
+        (0..self.bins.len())
+            .map(|i| {
+                let (mut sum, mut norm) = (0.0, 0.0);
+                for (k, weight) in weights.iter().enumerate() {
+                    let Some(bin) = (i + k).checked_sub(radius).and_then(|j| self.bins.get(j))
+                    else {
+                        continue;
+                    };
+                    sum += weight * bin.count;
+                    norm += weight;
+                }
+
+                sum / norm
+            })
+            .collect()
+    }
+
+    /// The index of the bin `value` falls in, or `None` outside the histogram's range.
+    ///
+    /// Bins include their upper edge, and the first bin its lower edge as well.
+    pub fn bin_index(&self, value: f64) -> Option<usize> {
+        let (&lower, &upper) = (self.edges.first()?, self.edges.last()?);
+        if !(lower..=upper).contains(&value) {
+            return None;
+        }
+
+        let i = self.edges.partition_point(|&edge| edge < value);
+        Some(i.saturating_sub(1).min(self.bins.len() - 1))
+    }
+
+    /// Adds the counts of `other` to this histogram's, for combining histograms of separate
+    /// shards of a dataset.
+    ///
+    /// An empty histogram takes on the bins of `other`. The [`Stats`] are combined if both have
+    /// them. Errors unless both have the same edges.
+    pub fn merge(&mut self, other: &Histogram) -> Result<(), Error> {
+        if self.edges.is_empty() {
+            *self = other.clone();
+            return Ok(());
+        }
+        if self.edges != other.edges {
+            return Err(Error::MismatchedEdges(
+                self.edges.clone(),
+                other.edges.clone(),
+            ));
+        }
+
+        for (bin, other) in self.bins.iter_mut().zip(&other.bins) {
+            bin.count += other.count;
+        }
+        self.underflow += other.underflow;
+        self.overflow += other.overflow;
+        self.stats = match (&self.stats, &other.stats) {
+            (Some(stats), Some(other)) => Some(stats.merge(other)),
+            _ => None,
+        };
+
+        Ok(())
+    }
+
+    /// The per-bin count of this histogram minus that of `other`, for comparing two datasets
+    /// binned the same way.
+    ///
+    /// Counts outside the edges are subtracted too, and the result has no [`Stats`]. Errors
+    /// unless both have the same edges.
+    pub fn diff(&self, other: &Histogram) -> Result<Histogram, Error> {
+        if self.edges != other.edges {
+            return Err(Error::MismatchedEdges(
+                self.edges.clone(),
+                other.edges.clone(),
+            ));
+        }
+
+        let mut diff = Histogram {
+            stats: None,
+            underflow: self.underflow - other.underflow,
+            overflow: self.overflow - other.overflow,
+            ..self.clone()
+        };
+        for (bin, other) in diff.bins.iter_mut().zip(&other.bins) {
+            bin.count -= other.count;
+        }
+
+        Ok(diff)
+    }
+
+    /// The Jensen–Shannon divergence between the distributions of this histogram's counts and
+    /// those of `other`, in bits: 0 when their bins hold the same shares of the values, up to 1
+    /// when no bin holds values of both.
+    ///
+    /// Counts outside the edges are left out. Errors unless both have the same edges and values
+    /// in their bins.
+    pub fn js_divergence(&self, other: &Histogram) -> Result<f64, Error> {
+        if self.edges != other.edges {
+            return Err(Error::MismatchedEdges(
+                self.edges.clone(),
+                other.edges.clone(),
+            ));
+        }
+        let totals = [self, other].map(|histo| histo.bins.iter().map(|bin| bin.count).sum::<f64>());
+        if totals.iter().any(|&total| total <= 0.0) {
+            return Err(Error::NoValues);
+        }
+
+        // Each distribution's half of the divergence from their mixture `m`.
+        let term = |p: f64, m: f64| {
+            if p > 0.0 {
+                p * (p / m).log2() / 2.0
+            } else {
+                0.0
+            }
+        };
+        Ok(self
+            .bins
+            .iter()
+            .zip(&other.bins)
+            .map(|(a, b)| {
+                let (p, q) = (a.count / totals[0], b.count / totals[1]);
+                let m = (p + q) / 2.0;
+                term(p, m) + term(q, m)
+            })
+            .sum())
+    }
+
+    /// The highest bin count, or `None` for a histogram without bins.
+    pub fn max_count(&self) -> Option<f64> {
+        self.bins.iter().map(|bin| bin.count).reduce(f64::max)
+    }
+
+    /// The bins with the highest count in ascending order, several if they tie, or none for
+    /// a histogram without bins.
+    pub fn mode(&self) -> Vec<&Bin> {
+        let max = self.max_count();
+        self.bins
+            .iter()
+            .filter(|bin| Some(bin.count) == max)
+            .collect()
+    }
+
+    /// Estimates the `q` quantile, for `q` from 0 to 1, assuming the values in each bin are
+    /// spread evenly across it. Returns `None` for an empty histogram or `q` outside 0 to 1.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let total: f64 = self.bins.iter().map(|bin| bin.count).sum();
+        if !(0.0..=1.0).contains(&q) || total <= 0.0 {
+            return None;
+        }
+
+        let target = q * total;
+        let mut below = 0.0;
+        for (i, bin) in self.bins.iter().enumerate() {
+            if bin.count > 0.0 && below + bin.count >= target {
+                let (lower, upper) = (self.edges[i], self.edges[i + 1]);
+                let fraction = ((target - below) / bin.count).clamp(0.0, 1.0);
+                return Some(lower + fraction * (upper - lower));
+            }
+            below += bin.count;
+        }
+
+        // Rounding left the target just above the total.
+        self.edges.last().copied()
+    }
+
+    /// Estimates the median, as [`Histogram::quantile`] of 0.5.
+    pub fn median(&self) -> Option<f64> {
+        self.quantile(0.5)
+    }
+
+    /// Returns the histogram with each bin counting itself and every bin before it.
+    pub fn cumulative(&self) -> Histogram {
+        let mut total = 0.0;
+        let bins = self
+            .bins
+            .iter()
+            .map(|bin| {
+                total += bin.count;
+                Bin {
+                    count: total,
+                    ..bin.clone()
+                }
+            })
+            .collect();
+
+        Histogram {
+            bins,
+            edges: self.edges.clone(),
+            stats: self.stats.clone(),
+            ..*self
+        }
+    }
+
+    /// The boundaries between bins in ascending order, one more than the number of bins.
+    pub fn edges(&self) -> &[f64] {
+        &self.edges
+    }
+
+    /// How many values fell below the first edge of an explicit range or set of edges, whether
+    /// they were then dropped or clipped into the first bin.
+    pub fn underflow(&self) -> f64 {
+        self.underflow
+    }
+
+    /// How many values fell above the last edge, as [`Histogram::underflow`] counts those below.
+    pub fn overflow(&self) -> f64 {
+        self.overflow
+    }
+
+    /// The statistics of the counted values, if the histogram was built from them.
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// The bins in ascending order, without consuming the histogram like
+    /// [`Histogram::into_bins`].
+    pub fn bins(&self) -> &[Bin] {
+        &self.bins
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Bin> {
+        self.bins.iter()
+    }
+
+    /// The number of bins.
+    pub fn len(&self) -> usize {
+        self.bins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    /// A horizontal bar chart of the bins in ascending order, one line for each with its label,
+    /// a bar of `#`s as long as `width` for the largest count, and its count.
+    pub fn render(&self, width: usize) -> String {
+        self.chart(width).render()
+    }
+
+    /// [`Histogram::render`] with a `+` where each bin's bar would end at its `reference`
+    /// count, e.g. what a fitted distribution expects of it, on the same scale as the bars.
+    ///
+    /// # Panics
+    ///
+    /// If `reference` does not have a count for each bin.
+    pub fn render_overlay(&self, width: usize, reference: &[f64]) -> String {
+        self.chart(width).reference(reference).render()
+    }
+
+    /// A [`Chart`] of the bins with bars up to `width` long, for charts [`Histogram::render`]
+    /// cannot draw.
+    pub fn chart(&self, width: usize) -> Chart<'_> {
+        Chart {
+            histo: self,
+            width,
+            reference: None,
+            log_scale: false,
+            title: None,
+            labels: (None, None),
+            quantiles: &[],
+        }
+    }
+
+    pub fn into_bins(self) -> Vec<Bin> {
+        self.bins
+    }
+
+    pub fn into_edges(self) -> Vec<f64> {
+        self.edges
+    }
+
+    pub fn into_counts(self) -> Vec<f64> {
+        self.bins.into_iter().map(|bin| bin.count).collect()
+    }
+
+    pub fn into_labels(self) -> Vec<f64> {
+        self.bins.into_iter().map(|bin| bin.label).collect()
+    }
+}
+
+impl fmt::Display for Histogram {
+    /// Renders the histogram as [`Histogram::render`] does, [`DISPLAY_WIDTH`] wide.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(DISPLAY_WIDTH))
+    }
+}
+
+impl FromIterator<f64> for Histogram {
+    /// Bins the values evenly like [`Histogram::auto`] with [`AutoBins::Sturges`], since their
+    /// count is not known until they are all collected. Values that `auto` refuses, e.g. none,
+    /// collect into an empty histogram.
+    fn from_iter<I: IntoIterator<Item = f64>>(values: I) -> Self {
+        Histogram::auto(values.into_iter().collect(), AutoBins::Sturges).unwrap_or_default()
+    }
+}
+
+impl IntoIterator for Histogram {
+    type Item = Bin;
+    type IntoIter = std::vec::IntoIter<Bin>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.bins.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Histogram {
+    type Item = &'a Bin;
+    type IntoIter = std::slice::Iter<'a, Bin>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.bins.iter()
+    }
+}
+
+/// A horizontal bar chart of a [`Histogram`]'s bins, from [`Histogram::chart`], drawn by
+/// [`Chart::render`].
+#[derive(Debug, Clone, Copy)]
+pub struct Chart<'a> {
+    histo: &'a Histogram,
+    width: usize,
+    reference: Option<&'a [f64]>,
+    log_scale: bool,
+    title: Option<&'a str>,
+    labels: (Option<&'a str>, Option<&'a str>),
+    quantiles: &'a [f64],
+}
+
+impl<'a> Chart<'a> {
+    /// Marks a `+` where each bin's bar would end at its `reference` count, as
+    /// [`Histogram::render_overlay`] does.
+    ///
+    /// # Panics
+    ///
+    /// If `reference` does not have a count for each bin.
+    pub fn reference(mut self, reference: &'a [f64]) -> Self {
+        assert_eq!(
+            reference.len(),
+            self.histo.bins.len(),
+            "one reference count per bin"
+        );
+        self.reference = Some(reference);
+        self
+    }
+
+    /// Scales the bars by the logarithm of one more than their count, so bins many orders of
+    /// magnitude below the fullest still get bars, and empty bins still get none.
+    pub fn log_scale(mut self, log_scale: bool) -> Self {
+        self.log_scale = log_scale;
+        self
+    }
+
+    /// A `title` line above the chart, if there is one.
+    pub fn title(mut self, title: Option<&'a str>) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// A header line naming what the bin labels and the bars measure, over their columns.
+    pub fn axis_labels(mut self, x: Option<&'a str>, y: Option<&'a str>) -> Self {
+        self.labels = (x, y);
+        self
+    }
+
+    /// Marks the bin holding each of `quantiles`, from 0 to 1, with the quantile and its value
+    /// as [`Histogram::quantile`] estimates it, e.g. `<- p50 4.25, p95 9.10` after the count.
+    pub fn quantiles(mut self, quantiles: &'a [f64]) -> Self {
+        self.quantiles = quantiles;
+        self
+    }
+
+    /// Any title and axis labels, then one line for each bin in ascending order with its label,
+    /// its bar, its count and any quantile marks.
+    pub fn render(&self) -> String {
+        let Chart {
+            histo,
+            width,
+            reference,
+            log_scale,
+            title,
+            labels: (x_label, y_label),
+            quantiles,
+        } = *self;
+        let labels: Vec<String> = histo
+            .bins
+            .iter()
+            .map(|bin| format!("{:0.2}", bin.label))
+            .collect();
+        let label_width = labels
+            .iter()
+            .map(String::len)
+            .chain(x_label.map(|label| label.chars().count()))
+            .max()
+            .unwrap_or(0);
+        let scale = |count: f64| match log_scale {
+            true => count.max(0.0).ln_1p(),
+            false => count.max(0.0),
+        };
+        let max = histo
+            .bins
+            .iter()
+            .map(|bin| bin.count)
+            .chain(reference.into_iter().flatten().copied())
+            .map(scale)
+            .fold(0.0, f64::max);
+        let length = |count: f64| match max > 0.0 {
+            true => (scale(count) / max * width as f64).round() as usize,
+            false => 0,
+        };
+
+        let mut marks: Vec<Vec<String>> = vec![Vec::new(); histo.bins.len()];
+        for &q in quantiles {
+            let Some(value) = histo.quantile(q) else {
+                continue;
+            };
+            if let Some(i) = histo.bin_index(value) {
+                // Rounded so that e.g. 0.999 reads as p99.9 despite the float product.
+                let percent = (q * 100.0 * 1e6).round() / 1e6;
+                marks[i].push(format!("p{} {:0.2}", percent, value));
+            }
+        }
+        let count_width = histo
+            .bins
+            .iter()
+            .map(|bin| bin.count.to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        let mut chart = String::new();
+        if let Some(title) = title {
+            chart.push_str(&format!("{}\n", title));
+        }
+        if x_label.is_some() || y_label.is_some() {
+            let header = format!(
+                "{:>label_width$} | {}",
+                x_label.unwrap_or_default(),
+                y_label.unwrap_or_default()
+            );
+            chart.push_str(&format!("{}\n", header.trim_end()));
+        }
+        for (i, (label, bin)) in labels.iter().zip(&histo.bins).enumerate() {
+            let mut bar = "#".repeat(length(bin.count));
+            // The mark sits just past a bar of the reference count's length, so one more
+            // column is needed for a mark at the full width.
+            let width = match reference {
+                Some(reference) => {
+                    let mut cells: Vec<char> = format!("{:<1$}", bar, width + 1).chars().collect();
+                    cells[length(reference[i])] = '+';
+                    bar = cells.into_iter().collect();
+                    width + 1
+                }
+                None => width,
+            };
+            let count = match marks[i].is_empty() {
+                true => bin.count.to_string(),
+                false => format!("{:<count_width$}  <- {}", bin.count, marks[i].join(", ")),
+            };
+            chart.push_str(&format!(
+                "{:>label_width$} | {:<width$} {}\n",
+                label, bar, count
+            ));
+        }
+
+        chart
+    }
+}
+
+/// A numeric type whose values can be binned, converted to `f64` one at a time as they are
+/// counted so that no `f64` copy of the whole input is made.
+///
+/// The exceptions are the binnings that sort the values or keep only some of them:
+/// [`Histogram::discrete`], [`Histogram::log_spaced`], [`Histogram::from_weighted_values`] and
+/// [`AutoBins::FreedmanDiaconis`] work on an `f64` copy.
+pub trait Sample: Copy {
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_sample {
+    ($($t:ty),*) => {
+        $(impl Sample for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        })*
+    };
+}
+
+impl_sample!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// Counts values into uniform bins one at a time, so the values never need to be held at once.
+///
+/// Unlike [`Histogram::from_values`] the range has to be known up front.
+#[derive(Debug, Clone)]
+pub struct HistogramBuilder {
+    lower: f64,
+    upper: f64,
+    num_bins: usize,
+    out_of_range: OutOfRange,
+    counts: Counts,
+    moments: Moments,
+    underflow: f64,
+    overflow: f64,
+}
+
+/// What happens to NaN and infinite values when binning.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum NonFinitePolicy {
+    /// Leave them uncounted.
+    #[default]
+    Skip,
+    /// Refuse them with [`Error::NonFinite`].
+    Error,
+    /// Count infinities in the nearest end bin. NaN has no nearest end and is left uncounted.
+    ClampToEdges,
+}
+
+impl NonFinitePolicy {
+    /// `values` with their NaN and infinities dealt with, clamping infinities to `lower` and
+    /// `upper`, which should be finite.
+    pub fn apply(self, values: Vec<f64>, lower: f64, upper: f64) -> Result<Vec<f64>, Error> {
+        values
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, value)| self.apply_one(i, value, lower, upper).transpose())
+            .collect()
+    }
+
+    /// The value to count for `value`, the `index`th, or `None` if it is left uncounted.
+    pub fn apply_one(
+        self,
+        index: usize,
+        value: f64,
+        lower: f64,
+        upper: f64,
+    ) -> Result<Option<f64>, Error> {
+        match (self, value) {
+            (_, value) if value.is_finite() => Ok(Some(value)),
+            (NonFinitePolicy::Error, value) => Err(Error::NonFinite(index, value)),
+            (NonFinitePolicy::ClampToEdges, f64::NEG_INFINITY) => Ok(Some(lower)),
+            (NonFinitePolicy::ClampToEdges, f64::INFINITY) => Ok(Some(upper)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// What happens to values outside a histogram's explicit range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutOfRange {
+    /// Leave them uncounted.
+    Drop,
+    /// Count them in the nearest end bin.
+    Clip,
+}
+
+/// The counts of a [`HistogramBuilder`]'s bins, every one of them or only those counted into.
+#[derive(Debug, Clone)]
+enum Counts {
+    Dense(Vec<f64>),
+    Sparse(BTreeMap<usize, f64>),
+}
+
+impl HistogramBuilder {
+    /// Starts `num_bins` empty bins evenly spanning `lower` to `upper`, dropping values outside
+    /// them. From [`SPARSE_BINS`] bins on only the bins counted into are stored, as
+    /// [`HistogramBuilder::sparse`] does.
+    ///
+    /// Errors unless there is at least one bin and `lower` is finite and below the finite
+    /// `upper`.
+    pub fn new(lower: f64, upper: f64, num_bins: usize) -> Result<Self, Error> {
+        match num_bins >= SPARSE_BINS {
+            true => Self::with_counts(lower, upper, num_bins, Counts::Sparse(BTreeMap::new())),
+            false => Self::with_counts(lower, upper, num_bins, Counts::Dense(vec![0.0; num_bins])),
+        }
+    }
+
+    /// Starts bins like [`HistogramBuilder::new`], storing counts only for the bins values land
+    /// in however few bins there are, e.g. for a fine grained scan of a wide range.
+    pub fn sparse(lower: f64, upper: f64, num_bins: usize) -> Result<Self, Error> {
+        Self::with_counts(lower, upper, num_bins, Counts::Sparse(BTreeMap::new()))
+    }
+
+    fn with_counts(lower: f64, upper: f64, num_bins: usize, counts: Counts) -> Result<Self, Error> {
+        if num_bins == 0 {
+            return Err(Error::NoBins);
+        }
+        if !(lower.is_finite() && upper.is_finite() && lower < upper) {
+            return Err(Error::InvalidRange(lower, upper));
+        }
+
+        Ok(Self {
+            lower,
+            upper,
+            num_bins,
+            out_of_range: OutOfRange::Drop,
+            counts,
+            moments: Moments::default(),
+            underflow: 0.0,
+            overflow: 0.0,
+        })
+    }
+
+    pub fn out_of_range(mut self, out_of_range: OutOfRange) -> Self {
+        self.out_of_range = out_of_range;
+        self
+    }
+
+    /// Counts `value`, unless it falls outside the builder's range and is dropped. NaN is
+    /// never counted.
+    pub fn push<T: Sample>(&mut self, value: T) {
+        self.push_weighted(value, 1.0);
+    }
+
+    /// Adds `weight` to the bin `value` falls in, as [`HistogramBuilder::push`] counts it.
+    pub fn push_weighted<T: Sample>(&mut self, value: T, weight: f64) {
+        let value = value.to_f64();
+        if value < self.lower {
+            self.underflow += weight;
+        } else if value > self.upper {
+            self.overflow += weight;
+        }
+        let binned = match self.out_of_range {
+            _ if value.is_nan() => return,
+            OutOfRange::Clip => value.clamp(self.lower, self.upper),
+            OutOfRange::Drop if !(self.lower..=self.upper).contains(&value) => return,
+            OutOfRange::Drop => value,
+        };
+        self.moments.push(value, weight);
+
+        let i = uniform_index(self.lower, self.upper, self.num_bins, binned);
+        match &mut self.counts {
+            Counts::Dense(counts) => counts[i] += weight,
+            Counts::Sparse(counts) => *counts.entry(i).or_insert(0.0) += weight,
+        }
+    }
+
+    /// The histogram of the values pushed, with every bin stored however the counts were.
+    pub fn finish(self) -> Histogram {
+        self.finish_sparse().into_histogram()
+    }
+
+    /// The histogram of the values pushed, storing only the bins counted into.
+    pub fn finish_sparse(self) -> SparseHistogram {
+        let counts = match self.counts {
+            Counts::Dense(counts) => counts
+                .into_iter()
+                .enumerate()
+                .filter(|&(_, count)| count != 0.0)
+                .collect(),
+            Counts::Sparse(counts) => counts,
+        };
+
+        SparseHistogram {
+            lower: self.lower,
+            upper: self.upper,
+            num_bins: self.num_bins,
+            counts,
+            stats: self.moments.finish(),
+            underflow: self.underflow,
+            overflow: self.overflow,
+        }
+    }
+}
+
+/// A histogram of even bins that stores only the counts of bins values landed in, for when
+/// most of a great many bins are empty.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SparseHistogram {
+    lower: f64,
+    upper: f64,
+    num_bins: usize,
+    /// The counts of the bins counted into, by bin index.
+    counts: BTreeMap<usize, f64>,
+    stats: Option<Stats>,
+    underflow: f64,
+    overflow: f64,
+}
+
+impl SparseHistogram {
+    /// Counts `values` into `num_bins` even bins spanning `lower` to `upper`, like
+    /// [`Histogram::with_range`] but storing only the bins counted into.
+    pub fn with_range<T: Sample>(
+        values: &[T],
+        num_bins: usize,
+        lower: f64,
+        upper: f64,
+        out_of_range: OutOfRange,
+    ) -> Result<Self, Error> {
+        let mut builder =
+            HistogramBuilder::sparse(lower, upper, num_bins)?.out_of_range(out_of_range);
+        builder.extend(values.iter().copied());
+        Ok(builder.finish_sparse())
+    }
+
+    /// The `i`th bin, counted into or not.
+    pub fn bin(&self, i: usize) -> Option<Bin> {
+        (i < self.num_bins).then(|| self.bin_at(i, self.count(i)))
+    }
+
+    /// The count of the `i`th bin, zero if no value landed in it.
+    pub fn count(&self, i: usize) -> f64 {
+        self.counts.get(&i).copied().unwrap_or(0.0)
+    }
+
+    /// The bins counted into, in ascending order.
+    pub fn nonempty(&self) -> impl Iterator<Item = Bin> + '_ {
+        self.counts.iter().map(|(&i, &count)| self.bin_at(i, count))
+    }
+
+    /// The number of bins, empty or not.
+    pub fn len(&self) -> usize {
+        self.num_bins
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.num_bins == 0
+    }
+
+    pub fn underflow(&self) -> f64 {
+        self.underflow
+    }
+
+    pub fn overflow(&self) -> f64 {
+        self.overflow
+    }
+
+    pub fn stats(&self) -> Option<&Stats> {
+        self.stats.as_ref()
+    }
+
+    /// Every bin in ascending order, the empty ones included.
+    pub fn into_bins(self) -> Vec<Bin> {
+        (0..self.num_bins)
+            .map(|i| self.bin_at(i, self.count(i)))
+            .collect()
+    }
+
+    /// The dense [`Histogram`] of the same bins.
+    pub fn into_histogram(self) -> Histogram {
+        let edges = (0..=self.num_bins).map(|i| self.edge(i)).collect();
+        let (stats, underflow, overflow) = (self.stats.clone(), self.underflow, self.overflow);
+
+        Histogram {
+            bins: self.into_bins(),
+            edges,
+            stats,
+            underflow,
+            overflow,
+        }
+    }
+
+    fn bin_width(&self) -> f64 {
+        (self.upper - self.lower) / self.num_bins as f64
+    }
+
+    fn edge(&self, i: usize) -> f64 {
+        i as f64 * self.bin_width() + self.lower
+    }
+
+    fn bin_at(&self, i: usize, count: f64) -> Bin {
+        Bin {
+            label: self.edge(i) + self.bin_width() / 2.0,
+            lower: self.edge(i),
+            upper: self.edge(i + 1),
+            count,
+        }
+    }
+}
+
+impl<T: Sample> Extend<T> for HistogramBuilder {
+    /// Pushes each of `values` as [`HistogramBuilder::push`] does.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        values.into_iter().for_each(|value| self.push(value));
+    }
+}
+
+/// Summary statistics of a set of values, each weighted by how many times it occurs.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stats {
+    /// The number of values, or the sum of their weights if weighted.
+    pub count: f64,
+    pub mean: f64,
+    /// The population standard deviation.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stats {
+    /// The statistics of `values`, ignoring NaN, or `None` if there are no other values.
+    pub fn from_values<T: Sample>(values: &[T]) -> Option<Self> {
+        let mut moments = Moments::default();
+        for value in values {
+            let value = value.to_f64();
+            if !value.is_nan() {
+                moments.push(value, 1.0);
+            }
+        }
+        moments.finish()
+    }
+
+    /// The statistics of `(value, weight)` pairs, each value counting as much as its weight,
+    /// or `None` if no value other than NaN has a positive weight. NaN weights are ignored.
+    pub fn from_weighted_values<T: Sample>(values: &[(T, f64)]) -> Option<Self> {
+        let mut moments = Moments::default();
+        for &(value, weight) in values {
+            let value = value.to_f64();
+            if !value.is_nan() && !weight.is_nan() {
+                moments.push(value, weight);
+            }
+        }
+        moments.finish()
+    }
+
+    /// The statistics of the values behind both `self` and `other`.
+    pub fn merge(&self, other: &Stats) -> Stats {
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let mean = self.mean + delta * other.count / count;
+        let m2 = self.stddev.powi(2) * self.count
+            + other.stddev.powi(2) * other.count
+            + delta * delta * self.count * other.count / count;
+
+        Stats {
+            count,
+            mean,
+            stddev: (m2 / count).sqrt(),
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// Running weighted moments, by West's update of Welford's algorithm.
+#[derive(Debug, Clone, Default)]
+struct Moments {
+    weight: f64,
+    mean: f64,
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Moments {
+    fn push(&mut self, value: f64, weight: f64) {
+        if weight <= 0.0 {
+            return;
+        }
+        if self.weight == 0.0 {
+            (self.min, self.max) = (value, value);
+        }
+
+        self.weight += weight;
+        let delta = value - self.mean;
+        self.mean += delta * weight / self.weight;
+        self.m2 += weight * delta * (value - self.mean);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn finish(&self) -> Option<Stats> {
+        (self.weight > 0.0).then(|| Stats {
+            count: self.weight,
+            mean: self.mean,
+            stddev: (self.m2 / self.weight).sqrt(),
+            min: self.min,
+            max: self.max,
+        })
+    }
+}
+
+/// Counts of distinct string keys, such as status codes or host names.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CategoricalHistogram {
+    counts: BTreeMap<String, f64>,
+}
+
+impl CategoricalHistogram {
+    pub fn from_keys<I, K>(keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<str>,
+    {
+        let mut histogram = Self::default();
+        for key in keys {
+            histogram.push(key.as_ref());
+        }
+        histogram
+    }
+
+    pub fn push(&mut self, key: &str) {
+        self.push_weighted(key, 1.0);
+    }
+
+    /// Adds `weight` to the count of `key`, e.g. for pre-aggregated input.
+    pub fn push_weighted(&mut self, key: &str, weight: f64) {
+        match self.counts.get_mut(key) {
+            Some(count) => *count += weight,
+            None => {
+                self.counts.insert(key.to_string(), weight);
+            }
+        }
+    }
+
+    /// The count of `key`, zero if it was never pushed.
+    pub fn count(&self, key: &str) -> f64 {
+        self.counts.get(key).copied().unwrap_or(0.0)
+    }
+
+    /// The number of distinct keys.
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn total(&self) -> f64 {
+        self.counts.values().sum()
+    }
+
+    /// The keys and their counts, most frequent first and ties in key order.
+    pub fn into_sorted(self) -> Vec<(String, f64)> {
+        let mut counts: Vec<(String, f64)> = self.counts.into_iter().collect();
+        // The sort is stable, so ties keep the map's key order.
+        counts.sort_by(|a, b| b.1.total_cmp(&a.1));
+        counts
+    }
+}
+
+/// Counts of `(x, y)` pairs in a grid of uniform bins spanning the range of each axis.
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram2D {
+    x_edges: Vec<f64>,
+    y_edges: Vec<f64>,
+    /// The counts of each x bin's y bins in turn.
+    counts: Vec<f64>,
+}
+
+impl Histogram2D {
+    /// Bins `values` into `x_bins` by `y_bins` cells, binning each axis like
+    /// [`Histogram::from_values`] and erring as it does when an axis cannot be binned. Pairs
+    /// with a NaN are not counted.
+    pub fn from_values<T: Sample, U: Sample>(
+        values: &[(T, U)],
+        x_bins: usize,
+        y_bins: usize,
+    ) -> Result<Self, Error> {
+        let pairs: Vec<(f64, f64)> = values
+            .iter()
+            .map(|&(x, y)| (x.to_f64(), y.to_f64()))
+            .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+            .collect();
+        let range = |axis: fn(&(f64, f64)) -> f64| {
+            pairs
+                .iter()
+                .map(axis)
+                .fold(None, |acc: Option<(f64, f64)>, value| match acc {
+                    Some((min, max)) => Some((min.min(value), max.max(value))),
+                    None => Some((value, value)),
+                })
+        };
+        if x_bins == 0 || y_bins == 0 {
+            return Err(Error::NoBins);
+        }
+        let (Some((x_min, x_max)), Some((y_min, y_max))) = (range(|p| p.0), range(|p| p.1)) else {
+            return Err(Error::NoValues);
+        };
+        for (min, max) in [(x_min, x_max), (y_min, y_max)] {
+            if min == max {
+                return Err(match pairs.len() {
+                    1 => Error::SingleValue(min),
+                    _ => Error::IdenticalValues(min),
+                });
+            }
+        }
+
+        let mut counts = vec![0.0; x_bins * y_bins];
+        for (x, y) in pairs {
+            let i = uniform_index(x_min, x_max, x_bins, x);
+            let j = uniform_index(y_min, y_max, y_bins, y);
+            counts[i * y_bins + j] += 1.0;
+        }
+
+        Ok(Self {
+            x_edges: HistogramBuilder::new(x_min, x_max, x_bins)?.finish().edges,
+            y_edges: HistogramBuilder::new(y_min, y_max, y_bins)?.finish().edges,
+            counts,
+        })
+    }
+
+    pub fn x_edges(&self) -> &[f64] {
+        &self.x_edges
+    }
+
+    pub fn y_edges(&self) -> &[f64] {
+        &self.y_edges
+    }
+
+    /// The count of the cell in the `i`th x bin and `j`th y bin.
+    pub fn count(&self, i: usize, j: usize) -> f64 {
+        self.counts[i * (self.y_edges.len() - 1) + j]
+    }
+
+    /// `(x_label, y_label, count)` for each cell, labelled by bin midpoints, in x then y order.
+    pub fn into_bins(self) -> Vec<(f64, f64, f64)> {
+        let midpoints =
+            |edges: &[f64]| -> Vec<f64> { edges.windows(2).map(|w| (w[0] + w[1]) / 2.0).collect() };
+        let (x_labels, y_labels) = (midpoints(&self.x_edges), midpoints(&self.y_edges));
+        x_labels
+            .iter()
+            .flat_map(|&x| y_labels.iter().map(move |&y| (x, y)))
+            .zip(self.counts)
+            .map(|((x, y), count)| (x, y, count))
+            .collect()
+    }
+}
+
+/// The bin `value` falls in among `num_bins` even bins from `lower` to `upper`, which it must
+/// be between.
+fn uniform_index(lower: f64, upper: f64, num_bins: usize, value: f64) -> usize {
+    let i = ((value - lower) / (upper.next_up() - lower) * num_bins as f64).floor() as usize;
+    i.min(num_bins - 1)
+}
+
+/// Whether `edges` bound at least one bin and ascend strictly, which also rules out NaN.
+fn valid_edges(edges: &[f64]) -> bool {
+    edges.len() >= 2 && edges.windows(2).all(|edge| edge[0] < edge[1])
+}
+
+/// Returns the percentage of `values` at or below `value`, counting ties as half.
+pub fn percentile_rank(values: &[f64], value: f64) -> f64 {
+    let (below, equal) = values.iter().fold((0, 0), |(below, equal), &v| {
+        if v < value {
+            (below + 1, equal)
+        } else if v == value {
+            (below, equal + 1)
+        } else {
+            (below, equal)
+        }
+    });
+
+    100.0 * (below as f64 + 0.5 * equal as f64) / values.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rows_batch_stops_at_failures() {
+        let rows = ["1,2,3", "4,5,6", "7,x,9", "10,11,12"];
+        let mut values = Vec::new();
+        let parser = ColumnParser::<f64>::single(1, ",");
+        assert_eq!(
+            parser.parse_rows_batch(&rows, &mut values),
+            Err((2, Error::FailedParse("x".to_string(), "f64".to_string())))
+        );
+        assert_eq!(values, vec![2.0, 5.0]);
+
+        values.clear();
+        let parser = ColumnParser::<f64>::new(&[2, 0], ",");
+        assert_eq!(parser.parse_rows_batch(&rows[..2], &mut values), Ok(()));
+        assert_eq!(values, vec![3.0, 1.0, 6.0, 4.0]);
+    }
+
+    #[test]
+    fn parse_column_from_rows() {
+        let parser = ColumnParser::<f64>::single(1, ",");
+        let result: Vec<f64> = vec!["1.0,2.0,3.0", "4.0,5.0,6.0", "7.0,8.0,9.0"]
+            .into_iter()
+            .map(|row| parser.parse_row(row).unwrap()[0])
+            .collect();
+
+        assert_eq!(result, vec![2.0, 5.0, 8.0]);
+    }
+
+    #[test]
+    fn parse_columns_in_given_order() {
+        let parser = ColumnParser::<f64>::new(&[2, 0, 2], ",");
+        assert_eq!(parser.parse_row("1.0,2.0,3.0"), Ok(vec![3.0, 1.0, 3.0]));
+    }
+
+    #[test]
+    fn missing_column() {
+        let parser = ColumnParser::<f64>::new(&[1], ",");
+        let result: Vec<Result<_, Error>> = vec!["1.0,2.0,3.0", "4.0", "7.0,8.0,9.0"]
+            .into_iter()
+            .map(|row| parser.parse_row(row))
+            .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                Ok(vec![2.0]),
+                Err(Error::MissingColumn("4.0".to_string(), 1)),
+                Ok(vec![8.0])
+            ]
+        );
+    }
+
+    #[test]
+    fn failed_parse() {
+        let parser = ColumnParser::<f64>::new(&[1], ",");
+        let result: Vec<Result<_, Error>> =
+            vec!["1.0,2.0,3.0", "4.0,not_a_float,6.0", "7.0,8.0,9.0"]
+                .into_iter()
+                .map(|row| parser.parse_row(row))
+                .collect();
+
+        assert_eq!(
+            result,
+            vec![
+                Ok(vec![2.0]),
+                Err(Error::FailedParse(
+                    "not_a_float".to_string(),
+                    "f64".to_string()
+                )),
+                Ok(vec![8.0])
+            ]
+        );
+    }
+
+    #[test]
+    fn histogram_counts_from_values() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let histogram = Histogram::from_values(values, 3).unwrap();
+        assert_eq!(histogram.into_counts(), vec![5.0, 3.0, 2.0]);
+    }
+
+    #[test]
+    fn histogram_range_includes_first_value() {
+        let values = vec![-2.0, 2.0, -1.0];
+        let histogram = Histogram::from_values(values, 2).unwrap();
+        assert_eq!(histogram.into_counts(), vec![2.0, 1.0]);
+    }
+
+    #[test]
+    fn histogram_labels_from_values() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let histogram = Histogram::from_values(values, 3).unwrap();
+        assert_eq!(histogram.into_labels(), vec![0.5, 1.5, 2.5]);
+    }
+
+    #[test]
+    fn histogram_edges_from_values() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let histogram = Histogram::from_values(values, 3).unwrap();
+        assert_eq!(histogram.edges(), &[0.0, 1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn degenerate_values_fail() {
+        let histo = |values: Vec<f64>, num_bins| Histogram::from_values(values, num_bins);
+        assert_eq!(histo(vec![], 3), Err(Error::NoValues));
+        assert_eq!(histo(vec![f64::NAN], 3), Err(Error::NoValues));
+        assert_eq!(histo(vec![4.0, f64::NAN], 3), Err(Error::SingleValue(4.0)));
+        assert_eq!(histo(vec![4.0, 4.0], 3), Err(Error::IdenticalValues(4.0)));
+        assert_eq!(histo(vec![1.0, 4.0], 0), Err(Error::NoBins));
+        assert_eq!(
+            Histogram::log_spaced(&[2.0, 2.0, -1.0], 3),
+            Err(Error::IdenticalValues(2.0))
+        );
+    }
+
+    #[test]
+    fn bin_widths_align_to_the_origin() {
+        let histo = Histogram::with_bin_width(&[3.0, 5.0, 6.0, 12.0], 5.0, 0.0).unwrap();
+        assert_eq!(histo.edges(), &[0.0, 5.0, 10.0, 15.0]);
+        assert_eq!(histo.clone().into_counts(), vec![2.0, 1.0, 1.0]);
+        assert_eq!(
+            Histogram::with_bin_width(&[2.0, 2.0], 5.0, 1.0)
+                .unwrap()
+                .edges(),
+            &[1.0, 6.0]
+        );
+        assert_eq!(
+            Histogram::with_bin_width(&[-0.3, 0.25], 0.1, 0.0)
+                .unwrap()
+                .edges()
+                .len(),
+            7
+        );
+        assert_eq!(
+            Histogram::with_bin_width(&[1.0], 0.0, 0.0),
+            Err(Error::InvalidWidth(0.0))
+        );
+    }
+
+    #[test]
+    fn exponential_bins_narrow_toward_zero() {
+        let binning = Exponential {
+            base: 2.0,
+            significant_digits: 1,
+        };
+        let edges = binning.edges(&[0.0, 1.2, 3.0, -1.0]).unwrap();
+        let rounded: Vec<f64> = edges
+            .iter()
+            .map(|edge| (edge * 10.0).round() / 10.0)
+            .collect();
+        assert_eq!(
+            rounded,
+            [
+                0.0, 1.0, 1.1, 1.2, 1.3, 1.4, 1.5, 1.6, 1.7, 1.8, 1.9, 2.0, 2.2, 2.4, 2.6, 2.8, 3.0
+            ]
+        );
+
+        let histo = Histogram::binned(&[0.0, 1.2, 3.0, -1.0], &binning).unwrap();
+        assert_eq!(histo.underflow(), 1.0);
+        assert_eq!(histo.iter().map(|bin| bin.count).sum::<f64>(), 3.0);
+        assert!(matches!(
+            Exponential {
+                base: 1.0,
+                ..binning
+            }
+            .edges(&[1.0]),
+            Err(Error::InvalidBinning(_))
+        ));
+    }
+
+    #[test]
+    fn exponential_bins_stay_bounded() {
+        let binning = Exponential {
+            base: 2.0,
+            significant_digits: 5,
+        };
+        assert_eq!(
+            binning.edges(&[1e-300, 1e300]),
+            Err(Error::InvalidBinning(
+                "1e-300 to 1e300 at 5 significant digits needs about 199400000 bins, more than 1048576"
+                    .to_string()
+            ))
+        );
+
+        let edges = Exponential::default().edges(&[5e-324, 1e-300]).unwrap();
+        assert!(edges.len() < MAX_EXPONENTIAL_BINS);
+        assert!(edges[0] > 0.0);
+        assert!(edges.windows(2).all(|pair| pair[0] < pair[1]));
+        let histo = Histogram::binned(&[5e-324, 1e-300], &Exponential::default()).unwrap();
+        assert_eq!(histo.underflow(), 1.0);
+    }
+
+    #[test]
+    fn bin_index_includes_upper_edges() {
+        let histo = Histogram::from_parts(vec![0.0, 1.0, 2.0], vec![1.0, 1.0]);
+        assert_eq!(histo.bin_index(0.0), Some(0));
+        assert_eq!(histo.bin_index(1.0), Some(0));
+        assert_eq!(histo.bin_index(1.5), Some(1));
+        assert_eq!(histo.bin_index(2.0), Some(1));
+        assert_eq!(histo.bin_index(2.5), None);
+        assert_eq!(histo.bin_index(f64::NAN), None);
+    }
+
+    #[test]
+    fn builder_matches_from_values() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let mut builder = HistogramBuilder::new(0.0, 3.0, 3).unwrap();
+        for &value in &values {
+            builder.push(value);
+        }
+        builder.push(-1.0);
+        builder.push(f64::NAN);
+
+        let histogram = builder.finish();
+        assert_eq!(histogram.underflow(), 1.0);
+        assert_eq!(
+            histogram.into_bins(),
+            Histogram::from_values(values, 3).unwrap().into_bins()
+        );
+    }
+
+    #[test]
+    fn sparse_bins_store_only_counts() {
+        let values = [0.5, 999_999.5, 3.2, 3.7, -1.0];
+        let sparse =
+            SparseHistogram::with_range(&values, 1_000_000, 0.0, 1e6, OutOfRange::Drop).unwrap();
+        assert_eq!(sparse.len(), 1_000_000);
+        assert_eq!(sparse.nonempty().map(|bin| bin.count).sum::<f64>(), 4.0);
+        assert_eq!(
+            sparse.nonempty().map(|bin| bin.lower).collect::<Vec<_>>(),
+            vec![0.0, 3.0, 999_999.0]
+        );
+        assert_eq!((sparse.count(3), sparse.count(4)), (2.0, 0.0));
+        assert_eq!(sparse.underflow(), 1.0);
+
+        let values = [1.0, 2.0, 2.0, 9.0];
+        let dense = Histogram::with_range(&values, 8, 0.0, 10.0, OutOfRange::Drop).unwrap();
+        let sparse = SparseHistogram::with_range(&values, 8, 0.0, 10.0, OutOfRange::Drop).unwrap();
+        assert_eq!(sparse.bin(2), Some(dense.bins()[2].clone()));
+        assert_eq!(sparse.clone().into_bins(), dense.clone().into_bins());
+        assert_eq!(sparse.into_histogram(), dense);
+    }
+
+    #[test]
+    fn non_finite_policies() {
+        let values = vec![
+            f64::NEG_INFINITY,
+            0.0,
+            1.0,
+            2.0,
+            f64::NAN,
+            4.0,
+            f64::INFINITY,
+        ];
+        let histo = |policy| Histogram::from_values_with(values.clone(), 2, policy);
+
+        assert_eq!(
+            histo(NonFinitePolicy::Skip).unwrap().into_counts(),
+            vec![3.0, 1.0]
+        );
+        assert_eq!(
+            histo(NonFinitePolicy::ClampToEdges).unwrap().into_counts(),
+            vec![4.0, 2.0]
+        );
+        assert!(matches!(
+            histo(NonFinitePolicy::Error),
+            Err(Error::NonFinite(0, value)) if value == f64::NEG_INFINITY
+        ));
+    }
+
+    #[test]
+    fn with_edges_counts_into_irregular_bins() {
+        let values = [0.0, 0.5, 1.0, 3.0, 7.0, 50.0, 150.0];
+        let histo = Histogram::with_edges(&values, &[0.0, 1.0, 5.0, 10.0, 100.0]).unwrap();
+        assert_eq!(histo.clone().into_counts(), vec![3.0, 1.0, 1.0, 1.0]);
+        assert_eq!(histo.into_labels(), vec![0.5, 3.0, 7.5, 55.0]);
+        assert_eq!(
+            Histogram::with_edges(&values, &[1.0, 0.0]),
+            Err(Error::InvalidEdges(vec![1.0, 0.0]))
+        );
+    }
+
+    #[test]
+    fn with_range_drops_or_clips() {
+        let values = [-5.0, 0.5, 1.5, 9.0];
+        let dropped = Histogram::with_range(&values, 2, 0.0, 2.0, OutOfRange::Drop).unwrap();
+        assert_eq!(dropped.edges(), &[0.0, 1.0, 2.0]);
+        assert_eq!(dropped.into_counts(), vec![1.0, 1.0]);
+
+        let clipped = Histogram::with_range(&values, 2, 0.0, 2.0, OutOfRange::Clip).unwrap();
+        assert_eq!(clipped.into_counts(), vec![2.0, 2.0]);
+
+        assert_eq!(
+            Histogram::with_range(&values, 0, 0.0, 2.0, OutOfRange::Drop),
+            Err(Error::NoBins)
+        );
+        for (lower, upper) in [(2.0, 0.0), (1.0, 1.0), (0.0, f64::INFINITY)] {
+            assert_eq!(
+                Histogram::with_range(&values, 2, lower, upper, OutOfRange::Clip),
+                Err(Error::InvalidRange(lower, upper))
+            );
+        }
+    }
+
+    #[test]
+    fn log_spaced_grows_geometrically() {
+        let values = [1.0, 5.0, 10.0, 50.0, 100.0, -1.0, 0.0];
+        let histo = Histogram::log_spaced(&values, 2).unwrap();
+        assert_eq!(histo.edges()[0], 1.0);
+        assert!((histo.edges()[1] - 10.0).abs() < 1e-9);
+        assert_eq!(histo.edges()[2], 100.0);
+        assert_eq!(histo.into_counts(), vec![3.0, 2.0]);
+    }
+
+    #[test]
+    fn cumulative_counts_running_totals() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let histo = Histogram::from_values(values, 3).unwrap().cumulative();
+        assert_eq!(histo.into_counts(), vec![5.0, 8.0, 10.0]);
+    }
+
+    #[test]
+    fn normalized_and_density() {
+        let histo = Histogram::with_edges(&[0.5, 1.5, 2.0, 3.0], &[0.0, 1.0, 3.0]).unwrap();
+        assert_eq!(histo.normalized(), vec![0.25, 0.75]);
+        assert_eq!(histo.density(), vec![1.0, 1.5]);
+    }
+
+    #[test]
+    fn merge_adds_matching_bins() {
+        let mut histo = Histogram::with_edges(&[0.5, 1.5], &[0.0, 1.0, 2.0]).unwrap();
+        let other = Histogram::with_edges(&[1.5, 1.8], &[0.0, 1.0, 2.0]).unwrap();
+        histo.merge(&other).unwrap();
+        assert_eq!(histo.clone().into_counts(), vec![1.0, 3.0]);
+
+        let coarse = Histogram::with_edges(&[0.5], &[0.0, 2.0]).unwrap();
+        assert!(matches!(
+            histo.merge(&coarse),
+            Err(Error::MismatchedEdges(..))
+        ));
+    }
+
+    #[test]
+    fn weighted_values_add_their_weights() {
+        let values = [(0.0, 2.5), (1.0, 1.0), (2.0, 0.5), (3.0, 4.0)];
+        let histo = Histogram::from_weighted_values(&values, 2).unwrap();
+        assert_eq!(histo.into_counts(), vec![3.5, 4.5]);
+
+        let unweighed = [(1.0, f64::NAN), (f64::NAN, 1.0), (2.0, 1.0), (2.0, 3.0)];
+        assert_eq!(
+            Histogram::from_weighted_values(&unweighed, 2),
+            Err(Error::IdenticalValues(2.0))
+        );
+        assert_eq!(
+            Histogram::from_weighted_values(&[(1.0, f64::NAN)], 2),
+            Err(Error::NoValues)
+        );
+        assert_eq!(
+            Histogram::from_weighted_values(&values, 0),
+            Err(Error::NoBins)
+        );
+    }
+
+    #[test]
+    fn integer_samples_bin_like_floats() {
+        let counters: Vec<u16> = vec![2, 1, 2, 3, 3, 2, 0, 1, 1, 1];
+        let floats: Vec<f64> = counters.iter().map(|&c| c as f64).collect();
+        assert_eq!(
+            Histogram::from_values(counters, 3).unwrap(),
+            Histogram::from_values(floats, 3).unwrap()
+        );
+    }
+
+    #[test]
+    fn discrete_counts_exact_values() {
+        let rolls = [3, 1, 6, 3, 3, 1];
+        let histo = Histogram::discrete(&rolls);
+        assert_eq!(histo.edges(), &[0.0, 2.0, 4.5, 7.5]);
+        assert_eq!(histo.clone().into_labels(), vec![1.0, 3.0, 6.0]);
+        assert_eq!(histo.into_counts(), vec![2.0, 3.0, 1.0]);
+        assert_eq!(Histogram::discrete(&[2.0]).edges(), &[1.5, 2.5]);
+    }
+
+    #[test]
+    fn smooth_spreads_counts() {
+        let values = vec![0.0, 2.0, 2.0, 2.0, 4.0];
+        let histogram = Histogram::from_values(values, 5).unwrap();
+        assert_eq!(
+            histogram.smooth(Kernel::Boxcar(1)),
+            vec![0.5, 4.0 / 3.0, 1.0, 4.0 / 3.0, 0.5]
+        );
+
+        let smoothed = histogram.smooth(Kernel::Gaussian(1.0));
+        assert!(smoothed[2] < 3.0 && smoothed[1] > 0.0);
+        assert!(smoothed[1] == smoothed[3]);
+    }
+
+    #[test]
+    fn rebin_to_splits_proportionally() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let histogram = Histogram::from_values(values, 3).unwrap();
+
+        let rebinned = histogram.rebin_to(&[0.0, 1.5, 3.0]).unwrap();
+        assert_eq!(rebinned.edges(), &[0.0, 1.5, 3.0]);
+        assert_eq!(rebinned.into_counts(), vec![6.5, 3.5]);
+
+        let rebinned = histogram.rebin_to(&[1.0, 2.0, 10.0]).unwrap();
+        assert_eq!(rebinned.into_counts(), vec![3.0, 2.0]);
+
+        let weighted = Histogram::from_weighted_values(&[(0.0, 0.2), (3.0, 0.2)], 3).unwrap();
+        let rebinned = weighted.rebin_to(&[0.0, 1.5, 3.0]).unwrap();
+        assert_eq!(rebinned.into_counts(), vec![0.2, 0.2]);
+    }
+
+    #[test]
+    fn rebin_to_rejects_unsorted_edges() {
+        let histogram = Histogram::from_values(vec![1.0, 2.0], 2).unwrap();
+        assert_eq!(
+            histogram.rebin_to(&[1.0, 0.0]),
+            Err(Error::InvalidEdges(vec![1.0, 0.0]))
+        );
+        assert!(histogram.rebin_to(&[1.0]).is_err());
+    }
+
+    #[test]
+    fn anomalies_flag_spikes() {
+        let mut values = vec![0.5, 1.5, 2.5, 3.5, 4.5, 5.5, 6.5];
+        values.extend(std::iter::repeat_n(3.5, 20));
+        let histogram = Histogram::from_values(values, 7).unwrap();
+        assert_eq!(
+            histogram.anomalies(2, 3.0),
+            vec![false, false, false, true, false, false, false]
+        );
+    }
+
+    #[test]
+    fn percentile_rank_counts_ties_as_half() {
+        let values = vec![1.0, 2.0, 2.0, 3.0];
+        assert_eq!(percentile_rank(&values, 2.0), 50.0);
+        assert_eq!(percentile_rank(&values, 0.0), 0.0);
+        assert_eq!(percentile_rank(&values, 3.5), 100.0);
+    }
+
+    #[test]
+    fn categorical_histogram_sorts_by_count() {
+        let mut histogram = CategoricalHistogram::from_keys(["get", "post", "get", "put"]);
+        histogram.push_weighted("post", 0.5);
+        assert_eq!(histogram.count("get"), 2.0);
+        assert_eq!(histogram.count("delete"), 0.0);
+        assert_eq!((histogram.len(), histogram.total()), (3, 4.5));
+        assert_eq!(
+            histogram.into_sorted(),
+            vec![
+                ("get".to_string(), 2.0),
+                ("post".to_string(), 1.5),
+                ("put".to_string(), 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn histogram_2d_counts_pairs() {
+        let values = vec![(0, 0.0), (0, 1.0), (4, 1.0), (4, 1.0), (2, f64::NAN)];
+        let histogram = Histogram2D::from_values(&values, 2, 2).unwrap();
+        assert_eq!(histogram.x_edges(), &[0.0, 2.0, 4.0]);
+        assert_eq!(histogram.count(1, 1), 2.0);
+        assert_eq!(
+            histogram.into_bins(),
+            vec![
+                (1.0, 0.25, 1.0),
+                (1.0, 0.75, 1.0),
+                (3.0, 0.25, 0.0),
+                (3.0, 0.75, 2.0)
+            ]
+        );
+
+        assert_eq!(Histogram2D::from_values(&values, 2, 0), Err(Error::NoBins));
+        assert_eq!(
+            Histogram2D::from_values(&[(1, f64::NAN)], 2, 2),
+            Err(Error::NoValues)
+        );
+        assert_eq!(
+            Histogram2D::from_values(&[(0, 1.0), (4, 1.0)], 2, 2),
+            Err(Error::IdenticalValues(1.0))
+        );
+    }
+
+    #[test]
+    fn quantiles_interpolate_within_bins() {
+        let histogram = Histogram::with_edges(&[1.0, 2.0, 3.0, 12.0], &[0.0, 4.0, 20.0]).unwrap();
+        assert_eq!(histogram.quantile(0.0), Some(0.0));
+        assert_eq!(histogram.median(), Some(8.0 / 3.0));
+        assert_eq!(histogram.quantile(0.75), Some(4.0));
+        assert_eq!(histogram.quantile(1.0), Some(20.0));
+        assert_eq!(histogram.quantile(1.5), None);
+        assert_eq!(Histogram::default().median(), None);
+    }
+
+    #[test]
+    fn stats_follow_counted_values() {
+        let histogram = Histogram::from_values(vec![2, 4, 4, 4, 5, 5, 7, 9], 3).unwrap();
+        let stats = Stats {
+            count: 8.0,
+            mean: 5.0,
+            stddev: 2.0,
+            min: 2.0,
+            max: 9.0,
+        };
+        assert_eq!(histogram.stats(), Some(&stats));
+        let values = [2.0, 4.0, 4.0, 4.0, f64::NAN, 5.0, 5.0, 7.0, 9.0];
+        assert_eq!(Stats::from_values(&values), Some(stats.clone()));
+        let weighted = [
+            (2.0, 1.0),
+            (4.0, 3.0),
+            (5.0, 2.0),
+            (7.0, 1.0),
+            (9.0, 1.0),
+            (1.0, 0.0),
+        ];
+        assert_eq!(Stats::from_weighted_values(&weighted), Some(stats));
+        assert_eq!(Stats::from_weighted_values(&[(1.0, f64::NAN)]), None);
+
+        let clipped =
+            Histogram::with_range(&[1.0, 5.0, 20.0], 2, 0.0, 10.0, OutOfRange::Drop).unwrap();
+        assert_eq!(clipped.stats().map(|stats| stats.max), Some(5.0));
+
+        let mut merged = Histogram::with_edges(&[1.0, 2.0], &[0.0, 5.0, 10.0]).unwrap();
+        merged
+            .merge(&Histogram::with_edges(&[3.0, 6.0], &[0.0, 5.0, 10.0]).unwrap())
+            .unwrap();
+        let stats = merged.stats().unwrap();
+        assert_eq!((stats.count, stats.mean, stats.max), (4.0, 3.0, 6.0));
+        assert!((stats.stddev - 3.5f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn auto_bins_follow_rules() {
+        let values: Vec<f64> = (0..1000).map(|v| v as f64).collect();
+        assert_eq!(AutoBins::Sturges.num_bins(&values), 11);
+        assert_eq!(AutoBins::Rice.num_bins(&values), 20);
+        // A standard deviation of 288.7, so bins 100.7 wide over a range of 999.
+        assert_eq!(AutoBins::Scott.num_bins(&values), 10);
+        // An IQR of 499.5, so bins 99.9 wide.
+        assert_eq!(AutoBins::FreedmanDiaconis.num_bins(&values), 10);
+        assert_eq!(AutoBins::FreedmanDiaconis.num_bins(&[5, 5, 5, 5]), 3);
+        assert_eq!(
+            Histogram::auto(values, AutoBins::Rice)
+                .unwrap()
+                .into_counts()
+                .len(),
+            20
+        );
+    }
+
+    #[test]
+    fn binnings_take_any_sample() {
+        let ints: Vec<u32> = (0..100).collect();
+        let floats: Vec<f64> = ints.iter().map(|&v| v as f64).collect();
+        for rule in [AutoBins::Sturges, AutoBins::FreedmanDiaconis] {
+            assert_eq!(
+                Histogram::binned(&ints, &rule).unwrap(),
+                Histogram::binned(&floats, &rule).unwrap()
+            );
+            assert_eq!(
+                rule.edges(&ints).unwrap(),
+                Histogram::auto(floats.clone(), rule).unwrap().edges()
+            );
+        }
+        assert_eq!(
+            Histogram::binned(&[3u8, 3], &AutoBins::Sturges),
+            Err(Error::IdenticalValues(3.0))
+        );
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn serde_round_trip() {
+        let histogram =
+            Histogram::with_range(&[1.0, 2.0, 12.0], 2, 0.0, 10.0, OutOfRange::Drop).unwrap();
+        let error = Error::MismatchedEdges(vec![0.0, 1.0], vec![0.0, 2.0]);
+
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&(&histogram, &error), &mut bytes).unwrap();
+        assert_eq!(
+            ciborium::from_reader(&bytes[..]).ok(),
+            Some((histogram, error))
+        );
+    }
+
+    #[test]
+    fn rebin_merges_adjacent_bins() {
+        let values = vec![0.0, 1.0, 1.5, 2.5, 3.5, 4.0, 4.5, 11.0];
+        let histo = Histogram::with_range(&values, 5, 0.0, 5.0, OutOfRange::Drop).unwrap();
+        let rebinned = histo.rebin(2).unwrap();
+        assert_eq!(rebinned.edges(), &[0.0, 2.0, 4.0, 5.0]);
+        assert_eq!(rebinned.overflow(), 1.0);
+        assert_eq!(rebinned.stats(), histo.stats());
+        assert_eq!(rebinned.into_counts(), vec![3.0, 3.0, 1.0]);
+        assert_eq!(histo.rebin(1), Ok(histo.clone()));
+        assert!(matches!(histo.rebin(0), Err(Error::InvalidBinning(_))));
+    }
+
+    #[test]
+    fn diff_subtracts_counts() {
+        let a =
+            Histogram::with_range(&[1.0, 2.0, 2.0, 9.0], 2, 0.0, 10.0, OutOfRange::Drop).unwrap();
+        let b =
+            Histogram::with_range(&[3.0, 7.0, 8.0, 11.0], 2, 0.0, 10.0, OutOfRange::Drop).unwrap();
+        let diff = a.diff(&b).unwrap();
+        assert_eq!((diff.overflow(), diff.stats()), (-1.0, None));
+        assert_eq!(diff.into_counts(), vec![2.0, -1.0]);
+        let mismatched = a.diff(&Histogram::from_values(vec![1.0, 2.0], 2).unwrap());
+        assert_eq!(
+            mismatched.unwrap_err().to_string(),
+            "the histograms have different bins, 2 bins from 0 to 10 and 2 bins from 1 to 2"
+        );
+        let uneven = Histogram::with_edges(&[1.0], &[0.0, 2.0, 10.0]).unwrap();
+        assert_eq!(
+            a.diff(&uneven).unwrap_err().to_string(),
+            "the histograms split 2 bins from 0 to 10 differently"
+        );
+    }
+
+    #[test]
+    fn js_divergence_compares_shares() {
+        let edges = [0.0, 5.0, 10.0];
+        let a = Histogram::with_edges(&[1.0, 2.0, 2.0, 9.0], &edges).unwrap();
+        let b = Histogram::with_edges(&[3.0, 7.0, 8.0, 11.0], &edges).unwrap();
+        assert!((a.js_divergence(&b).unwrap() - 0.130_198).abs() < 1e-6);
+        assert_eq!(a.js_divergence(&a.clone()), Ok(0.0));
+
+        let low = Histogram::with_edges(&[1.0], &edges).unwrap();
+        let high = Histogram::with_edges(&[6.0, 7.0], &edges).unwrap();
+        assert_eq!(low.js_divergence(&high), Ok(1.0));
+        assert_eq!(
+            low.js_divergence(&Histogram::with_edges(&[11.0], &edges).unwrap()),
+            Err(Error::NoValues)
+        );
+        assert!(matches!(
+            a.js_divergence(&Histogram::from_values(vec![1.0, 2.0], 2).unwrap()),
+            Err(Error::MismatchedEdges(..))
+        ));
+    }
+
+    #[test]
+    fn histograms_from_iterators() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let collected: Histogram = values.iter().copied().collect();
+        assert_eq!(
+            collected,
+            Histogram::auto(values.clone(), AutoBins::Sturges).unwrap()
+        );
+
+        let mut builder = HistogramBuilder::new(0.0, 3.0, 3).unwrap();
+        builder.extend(values.iter().copied());
+        builder.extend([1u8, 2]);
+        assert_eq!(builder.finish().into_counts(), vec![6.0, 4.0, 2.0]);
+    }
+
+    #[test]
+    fn mode_finds_the_fullest_bins() {
+        let histo = Histogram::discrete(&[1, 2, 2, 3, 4, 4]);
+        let labels: Vec<f64> = histo.mode().iter().map(|bin| bin.label).collect();
+        assert_eq!((histo.max_count(), labels), (Some(2.0), vec![2.0, 4.0]));
+        assert_eq!(Histogram::default().max_count(), None);
+        assert!(Histogram::default().mode().is_empty());
+    }
+
+    #[test]
+    fn renders_bars() {
+        let histogram =
+            Histogram::from_values(vec![1.0, 1.0, 2.0, 9.0, 10.0, 10.0, 10.0, 10.0], 2).unwrap();
+        assert_eq!(
+            histogram.render(8),
+            "3.25 | #####    3\n7.75 | ######## 5\n"
+        );
+        assert_eq!(histogram.to_string(), histogram.render(DISPLAY_WIDTH));
+    }
+
+    #[test]
+    fn overlays_mark_reference_counts() {
+        let histogram =
+            Histogram::from_values(vec![1.0, 1.0, 2.0, 9.0, 10.0, 10.0, 10.0, 10.0], 2).unwrap();
+        assert_eq!(
+            histogram.render_overlay(8, &[4.0, 4.0]),
+            "3.25 | ##### +   3\n7.75 | ######+#  5\n"
+        );
+        assert_eq!(
+            histogram.render_overlay(8, &[0.0, 10.0]),
+            "3.25 | +#        3\n7.75 | ####    + 5\n"
+        );
+    }
+
+    #[test]
+    fn log_scale_charts_show_small_bins() {
+        let histogram = Histogram::from_parts(vec![0.0, 1.0, 2.0, 3.0], vec![999.0, 9.0, 0.0]);
+        assert_eq!(
+            histogram.render(6),
+            "0.50 | ###### 999\n1.50 |        9\n2.50 |        0\n"
+        );
+        assert_eq!(
+            histogram.chart(6).log_scale(true).render(),
+            "0.50 | ###### 999\n1.50 | ##     9\n2.50 |        0\n"
+        );
+    }
+
+    #[test]
+    fn charts_carry_titles_and_labels() {
+        let histogram = Histogram::from_parts(vec![0.0, 1.0, 2.0], vec![2.0, 1.0]);
+        assert_eq!(
+            histogram
+                .chart(4)
+                .title(Some("Latency"))
+                .axis_labels(Some("ms"), Some("requests"))
+                .render(),
+            "Latency\n  ms | requests\n0.50 | #### 2\n1.50 | ##   1\n"
+        );
+        assert_eq!(
+            histogram
+                .chart(4)
+                .axis_labels(Some("latency"), None)
+                .render(),
+            "latency |\n   0.50 | #### 2\n   1.50 | ##   1\n"
+        );
+    }
+
+    #[test]
+    fn charts_mark_quantiles() {
+        let histogram = Histogram::from_parts(vec![0.0, 1.0, 2.0, 3.0], vec![10.0, 5.0, 5.0]);
+        assert_eq!(
+            histogram.chart(4).quantiles(&[0.5, 0.9, 0.95]).render(),
+            "0.50 | #### 10  <- p50 1.00\n1.50 | ##   5\n2.50 | ##   5   <- p90 2.60, p95 2.80\n"
+        );
+        assert_eq!(
+            histogram.chart(4).quantiles(&[2.0]).render(),
+            histogram.render(4)
+        );
+    }
+
+    #[test]
+    fn bins_carry_their_edges() {
+        let histogram = Histogram::discrete(&[1, 2, 2, 4]);
+        let bins = histogram.bins();
+        assert_eq!(
+            (bins[1].lower, bins[1].label, bins[1].upper),
+            (1.5, 2.0, 3.0)
+        );
+        assert_eq!(histogram.len(), 3);
+        assert_eq!(
+            histogram.iter().map(|bin| bin.count).sum::<f64>(),
+            (&histogram).into_iter().map(|bin| bin.count).sum::<f64>()
+        );
+        assert_eq!(histogram.into_edges(), vec![0.5, 1.5, 3.0, 5.0]);
+    }
+
+    #[test]
+    fn out_of_range_values_flow_over() {
+        let values = [-1.0, 0.5, 11.0, 12.0, f64::NAN];
+        let clipped = Histogram::with_range(&values, 2, 0.0, 10.0, OutOfRange::Clip).unwrap();
+        assert_eq!((clipped.underflow(), clipped.overflow()), (1.0, 2.0));
+        assert_eq!(clipped.into_counts(), vec![2.0, 2.0]);
+
+        let mut histogram = Histogram::with_edges(&values, &[0.0, 1.0, 10.0]).unwrap();
+        histogram.merge(&histogram.clone()).unwrap();
+        assert_eq!((histogram.underflow(), histogram.overflow()), (2.0, 4.0));
+        assert_eq!(
+            Histogram::from_values(values.to_vec(), 3)
+                .unwrap()
+                .overflow(),
+            0.0
+        );
     }
 }
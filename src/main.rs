@@ -1,113 +1,5172 @@
-use clap::Parser;
-use meval::Context;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use meval::{Context, Expr};
 use regex::Regex;
-use rhisto::{ColumnParser, Histogram};
+use rhisto::{
+    CategoricalHistogram, ColumnParser, Exponential, Histogram, HistogramBuilder, Kernel,
+    NonFinitePolicy, Stats, analysis,
+    cache::LruCache,
+    kde::Kde,
+    rhst::Rhst,
+    schema::{Schema, Violation},
+    tdigest::TDigest,
+};
 use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
     fs::File,
-    io::{BufRead, BufReader, BufWriter, Write},
-    path::PathBuf,
+    io::{BufRead, BufReader, BufWriter, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::RecvTimeoutError,
+    },
 };
 
+/// The number of bins either side of a bin that form its baseline for `--flag-anomalies`.
+const ANOMALY_RADIUS: usize = 2;
+
+/// The round numbers checked by the `heaping` subcommand, finest first.
+const HEAPING_BASES: [u32; 3] = [5, 10, 100];
+
+/// The length of the longest bar `--format-out chart` draws.
+const CHART_WIDTH: usize = 40;
+
+/// How long `--push` waits to connect to the gateway, and for each read and write after.
+const PUSH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The number of lines `sniff` guesses the dialect from.
+const SNIFF_LINES: usize = 100;
+
+/// How many rows the reading thread hands over at once.
+const CHUNK_ROWS: usize = 4096;
+
+/// The bytes of memory each value is estimated to take while it is binned, counting the copy
+/// made to split off infinite values and spare vector capacity.
+const BYTES_PER_VALUE: u64 = 24;
+
+/// The number of rows checked for likely mistakes in reading the input.
+const DIAGNOSTIC_ROWS: usize = 100;
+
+/// How often `--progress` reports.
+const PROGRESS_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Set by the first Ctrl-C, after which input reading stops and the output is written.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
 fn main() {
-    let args = Args::parse();
+    let cli = Cli::parse();
 
-    let mut reader: Box<dyn BufRead> = match args.input {
-        Some(path_buf) => Box::new(BufReader::new(
-            File::open(&path_buf).expect("failed to open input file"),
+    ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::Relaxed) {
+            // A second Ctrl-C while blocked on input gives up on the output.
+            std::process::exit(130);
+        }
+    })
+    .expect("failed to set Ctrl-C handler");
+
+    let mut pager = (cli.pager && std::io::stdout().is_terminal())
+        .then(spawn_pager)
+        .flatten();
+    let mut out: Box<dyn Write> = match pager.as_mut().and_then(|pager| pager.stdin.take()) {
+        Some(stdin) => Box::new(BufWriter::new(stdin)),
+        None => Box::new(BufWriter::new(std::io::stdout())),
+    };
+    let mut exit_code = 0;
+    let result = match cli.command {
+        Some(Command::Rank { value, input }) => {
+            let values = read_values(input);
+            if values.is_empty() {
+                fail(rhisto::Error::NoValues);
+            }
+            writeln!(out, "{:0.2}", rhisto::percentile_rank(&values, value))
+        }
+        Some(Command::Heaping { min_ratio, input }) => {
+            let values = read_values(input);
+            heaping(&mut out, &values, min_ratio)
+        }
+        Some(Command::Benford { input }) => {
+            let values = read_values(input);
+            benford(&mut out, &values)
+        }
+        Some(Command::Trend {
+            input,
+            time_column,
+            window,
+            stat,
+        }) => trend(&mut out, &input, time_column, window, stat),
+        Some(Command::Extract {
+            input,
+            column,
+            expr,
+            condition,
+            delim,
+            skip_header,
+        }) => {
+            let rows = read_rows(input.as_deref(), skip_header, b'\n', false);
+            let condition = condition.map(|condition| Condition::new(&condition, &delim));
+            let fields = Fields {
+                columns: &column,
+                exprs: &expr,
+                condition: condition.as_ref(),
+            };
+            extract(&mut out, rows, fields, &delim, skip_header)
+        }
+        Some(Command::Compare {
+            other,
+            group_by,
+            num_bins,
+            ks,
+            qq,
+            input,
+        }) => match group_by {
+            Some(column) => compare_groups(&mut out, &input, column, num_bins),
+            None => {
+                let mut other_input = input.clone();
+                other_input.input = Some(other.expect("clap requires other without --group-by"));
+                compare(
+                    &mut out,
+                    &read_values(input),
+                    &read_values(other_input),
+                    ks,
+                    qq,
+                )
+            }
+        },
+        Some(Command::EmitFilter {
+            histogram,
+            bin,
+            column,
+            delim,
+        }) => {
+            let rhst: Rhst = read_parsed(&histogram);
+            writeln!(
+                out,
+                "{}",
+                awk_filter(rhst.histogram.edges(), bin, column, &delim)
+            )
+        }
+        Some(Command::Sniff { input }) => sniff(&mut out, &input),
+        Some(Command::Validate {
+            schema,
+            input,
+            delim,
+            skip_header,
+        }) => {
+            let schema: Schema = read_parsed(&schema);
+            let rows = read_rows(input.as_deref(), skip_header, b'\n', false);
+            validate(&mut out, &schema, rows, &delim, skip_header).map(|violations| {
+                if violations > 0 {
+                    exit_code = 1;
+                }
+            })
+        }
+        Some(Command::Merge { histograms }) => {
+            let mut merged = Histogram::default();
+            for path in &histograms {
+                let rhst: Rhst = read_parsed(path);
+                merged
+                    .merge(&rhst.histogram)
+                    .unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err)));
+            }
+            write!(out, "{}", Rhst::new(merged))
+        }
+        Some(Command::Diff { a, b }) => {
+            let [a, b] = [&a, &b].map(|path| read_parsed::<Rhst>(path).histogram);
+            let diff = a.diff(&b).unwrap_or_else(|err| fail(err));
+            write!(out, "{}", Rhst::new(diff))
+        }
+        Some(Command::Sample {
+            histogram,
+            rows,
+            seed,
+        }) => {
+            let rhst: Rhst = read_parsed(&histogram);
+            sample(&mut out, &rhst.histogram, rows, seed)
+        }
+        None => histogram(&mut out, cli.input, cli.histogram, &mut exit_code),
+    };
+
+    match result.and_then(|()| out.flush()) {
+        Ok(()) => {}
+        // The reader went away, e.g. `| head`, which is not worth a panic.
+        Err(err) if err.kind() == std::io::ErrorKind::BrokenPipe => {}
+        Err(err) => panic!("failed to write output: {}", err),
+    }
+
+    // Closing the pager's input lets it reach the end, then it exits when the user quits it.
+    drop(out);
+    if let Some(mut pager) = pager {
+        let _ = pager.wait();
+    }
+
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+}
+
+/// Starts `$PAGER`, or `less -R` to keep colors, reading what is written to its stdin.
+fn spawn_pager() -> Option<std::process::Child> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+    std::process::Command::new("sh")
+        .arg("-c")
+        .arg(&pager)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .ok()
+}
+
+/// Bins the input and writes the histogram, setting `exit_code` when an `--assert` is broken.
+fn histogram(
+    out: &mut dyn Write,
+    input: InputArgs,
+    args: HistogramArgs,
+    exit_code: &mut i32,
+) -> std::io::Result<()> {
+    if args.categorical {
+        return categorical(out, input, args);
+    }
+    if !args.group_by_file.is_empty() {
+        return group_by_file(out, input, args);
+    }
+    if let Some(column) = args.group_by {
+        let groups = read_groups(&input, column);
+        let source = input.input.clone();
+        return write_groups(out, &input, args, source, "--group-by", groups);
+    }
+    if args.normalize == Some(Normalize::WithinGroup) {
+        fail("`--normalize=within-group` needs `--group-by-file` or `--group-by` groups");
+    }
+    if let Some(points) = args.kde {
+        return kde(out, input, args, points);
+    }
+
+    let chart_options = [
+        ("--overlay", args.overlay.is_some()),
+        ("--log-y", args.log_y),
+        ("--title", args.title.is_some()),
+        ("--xlabel", args.xlabel.is_some()),
+        ("--ylabel", args.ylabel.is_some()),
+        ("--mark-quantiles", !args.mark_quantiles.is_empty()),
+    ];
+    if let Some((option, _)) = chart_options.iter().find(|(_, given)| *given)
+        && args.format_out != Format::Chart
+    {
+        fail(format!(
+            "`{}` is only drawn on `--format-out chart`",
+            option
+        ));
+    }
+
+    let delim = input.delim.clone();
+    let source = input.input.clone();
+    let infinity_bin = input.ratio.is_some() && input.zero_policy == ZeroPolicy::Infinity;
+    let binned = match (args.weight_column, args.tdigest, args.memory_limit) {
+        (Some(weight_column), _, _) => bin_weighted(&input, weight_column, &args),
+        (None, Some(compression), _) => bin_digest(&input, compression, infinity_bin, &args),
+        (None, None, Some(limit)) => {
+            let cache = args
+                .cache
+                .as_deref()
+                .map(|path| RangeCache::new(path, &input));
+            match cache.as_ref().and_then(RangeCache::get) {
+                Some((range, count)) => bin_cached(&input, range, count, infinity_bin, &args),
+                None => match read_values_within(input, limit, &tmpdir(&args)) {
+                    Ok(values) => {
+                        if let Some(cache) = &cache {
+                            cache.store(finite_range(&values), values.len());
+                        }
+                        bin_values(values, Vec::new(), infinity_bin, &args)
+                    }
+                    Err(spilled) => {
+                        if let Some(cache) = &cache {
+                            cache.store(spilled.range, spilled.count);
+                        }
+                        bin_spilled(spilled, infinity_bin, &args)
+                    }
+                },
+            }
+        }
+        (None, None, None) => {
+            let (values, rows) = read_values_and_rows(input, args.keep_samples.is_some());
+            bin_values(values, rows, infinity_bin, &args)
+        }
+    };
+    let Binned {
+        histo,
+        stats,
+        total,
+        infinite,
+        exceedances,
+        quantiles,
+        normality,
+        samples,
+        broken,
+        ..
+    } = binned;
+    for reason in &broken {
+        eprintln!("{}", reason);
+        *exit_code = 1;
+    }
+    let provenance = (!args.no_metadata).then(|| Provenance::new(source, total));
+    if let Some(path) = &args.export_edges {
+        std::fs::write(path, edges_json(histo.edges()))
+            .unwrap_or_else(|err| fail(format!("failed to write `{}`: {}", path.display(), err)));
+    }
+    if let Some(url) = &args.push {
+        push(url, &prometheus_histogram(&args.push_metric, &histo))
+            .unwrap_or_else(|err| fail(format!("failed to push histogram: {}", err)));
+    }
+
+    let anomalies = args
+        .flag_anomalies
+        .then(|| histo.anomalies(ANOMALY_RADIUS, args.anomaly_sigma));
+    let smoothed = args
+        .smooth
+        .map(|sigma| histo.smooth(Kernel::Gaussian(sigma)));
+
+    let scaled = if args.normalize.is_some() {
+        Some(histo.normalized())
+    } else if args.density {
+        Some(histo.density())
+    } else {
+        None
+    };
+
+    #[cfg(feature = "parquet")]
+    if args.format_out == Format::Parquet {
+        let path = args.output.expect("clap requires output for parquet");
+        write_parquet(&path, histo, provenance.as_ref()).expect("failed to write parquet output");
+        return Ok(());
+    }
+
+    #[cfg(feature = "sqlite")]
+    if args.format_out == Format::Sqlite {
+        let path = args.output.expect("clap requires output for sqlite");
+        write_sqlite(&path, &args.table, args.tag.as_deref(), histo)
+            .expect("failed to write sqlite output");
+        return Ok(());
+    }
+
+    let mut writer: Box<dyn Write + '_> = match &args.output {
+        Some(path_buf) => Box::new(BufWriter::new(
+            File::create(path_buf).expect("failed to open output file"),
         )),
-        None => Box::new(BufReader::new(std::io::stdin())),
+        None => Box::new(out),
     };
 
-    if args.skip_header {
-        reader.skip_until(b'\n').expect("failed to skip header");
+    #[cfg(feature = "template")]
+    if let Some(template) = &args.template {
+        let template = std::fs::read_to_string(template).expect("failed to read template");
+        let ctx = minijinja::context! {
+            bins => template_bins(histo, args.bin_labels.as_ref()),
+            stats => minijinja::Value::from(std::collections::BTreeMap::from(stats)),
+            num_bins => binned.num_bins,
+            metadata => provenance.map(|provenance| {
+                minijinja::Value::from(std::collections::BTreeMap::from(provenance.pairs()))
+            }),
+        };
+        let report = minijinja::Environment::new()
+            .render_str(&template, ctx)
+            .expect("failed to render template");
+        writer.write_all(report.as_bytes())?;
+        return writer.flush();
     }
 
-    let values: Vec<f64> = match args.column {
-        Some(column) => {
-            let parser = ColumnParser::<f64>::single(column, &args.delim);
-            reader
-                .lines()
-                .map(|row| row.unwrap())
-                .map(|row| parser.parse_row(&row).unwrap()[0])
-                .collect()
+    if args.format_out == Format::Rhst {
+        write!(writer, "{}", to_rhst(histo, stats, provenance))?;
+        return writer.flush();
+    }
+
+    if args.format_out == Format::Json {
+        write_json(
+            &mut writer,
+            histo,
+            args.bin_labels.as_ref(),
+            samples.as_deref(),
+            provenance.as_ref(),
+        )?;
+        return writer.flush();
+    }
+
+    #[cfg(feature = "cbor")]
+    if args.format_out == Format::Cbor {
+        ciborium::into_writer(&to_rhst(histo, stats, provenance), &mut writer).map_err(|err| {
+            match err {
+                ciborium::ser::Error::Io(err) => err,
+                ciborium::ser::Error::Value(msg) => std::io::Error::other(msg),
+            }
+        })?;
+        return writer.flush();
+    }
+
+    if args.format_out == Format::Chart {
+        let reference = args
+            .overlay
+            .as_ref()
+            .map(|overlay| overlay_counts(overlay, &histo, stats));
+        let mut chart = histo
+            .chart(CHART_WIDTH)
+            .log_scale(args.log_y)
+            .title(args.title.as_deref())
+            .axis_labels(args.xlabel.as_deref(), args.ylabel.as_deref())
+            .quantiles(&args.mark_quantiles);
+        if let Some(reference) = &reference {
+            chart = chart.reference(reference);
+        }
+        writer.write_all(chart.render().as_bytes())?;
+        return writer.flush();
+    }
+
+    if args.format_out != Format::Text {
+        let edges = histo.edges().to_vec();
+        let counts: Vec<String> = match scaled {
+            Some(scaled) => scaled.iter().map(|value| format!("{:?}", value)).collect(),
+            None => histo.into_counts().iter().map(f64::to_string).collect(),
+        };
+        write_literals(
+            &mut writer,
+            &edges,
+            &counts,
+            args.format_out,
+            provenance.as_ref(),
+        )?;
+        return writer.flush();
+    }
+
+    let flow = args.flow_bins.then(|| {
+        let edges = histo.edges();
+        let (lower, upper) = (edges[0], edges[edges.len() - 1]);
+        (
+            format!(
+                "<{}{}{}",
+                format_label(lower, &args),
+                &delim,
+                histo.underflow()
+            ),
+            format!(
+                ">{}{}{}",
+                format_label(upper, &args),
+                &delim,
+                histo.overflow()
+            ),
+        )
+    });
+
+    let (negative, positive) = infinite;
+    if infinity_bin {
+        let first = if args.descending {
+            ("inf", positive)
+        } else {
+            ("-inf", negative)
+        };
+        writeln!(writer, "{}{}{}", first.0, &delim, first.1)?;
+    }
+    if let Some((underflow, overflow)) = &flow {
+        writeln!(
+            writer,
+            "{}",
+            if args.descending { overflow } else { underflow }
+        )?;
+    }
+
+    let bins = histo.into_bins();
+    let mut order: Vec<usize> = (0..bins.len()).collect();
+    if args.descending {
+        order.reverse();
+    }
+
+    for i in order {
+        let bin = &bins[i];
+        let label = bin_label(i, bin.label, &args);
+        match &scaled {
+            Some(scaled) => write!(writer, "{}{}{:0.4}", label, &delim, scaled[i])?,
+            None => write!(writer, "{}{}{}", label, &delim, bin.count)?,
+        };
+        if let Some(smoothed) = &smoothed {
+            write!(writer, "{}{:0.2}", &delim, smoothed[i])?;
+        }
+        if let Some(anomalies) = &anomalies {
+            write!(writer, "{}{}", &delim, if anomalies[i] { "*" } else { "" })?;
+        }
+        writeln!(writer)?;
+    }
+
+    if let Some((underflow, overflow)) = &flow {
+        writeln!(
+            writer,
+            "{}",
+            if args.descending { underflow } else { overflow }
+        )?;
+    }
+    if infinity_bin {
+        let last = if args.descending {
+            ("-inf", negative)
+        } else {
+            ("inf", positive)
+        };
+        writeln!(writer, "{}{}{}", last.0, &delim, last.1)?;
+    }
+
+    if !args.thresholds.is_empty() {
+        writeln!(writer)?;
+    }
+
+    for (threshold, count) in args.thresholds.iter().zip(exceedances) {
+        let fraction = count as f64 / total as f64;
+        writeln!(
+            writer,
+            ">{}{}{}{}{:0.4}",
+            threshold, &delim, count, &delim, fraction
+        )?;
+    }
+
+    if !quantiles.is_empty() {
+        writeln!(writer)?;
+    }
+    for (q, value) in args.exact_quantiles.iter().zip(quantiles) {
+        // Rounded so that e.g. 0.999 reads as p99.9 despite the float product.
+        let percent = (q * 100.0 * 1e6).round() / 1e6;
+        writeln!(writer, "p{}{}{}", percent, &delim, value)?;
+    }
+
+    if let Some(normality) = normality {
+        writeln!(writer)?;
+        match normality {
+            Some((a2, p_value)) => {
+                writeln!(writer, "ad_statistic{}{:0.4}", &delim, a2)?;
+                writeln!(writer, "ad_p_value{}{:0.4}", &delim, p_value)?;
+            }
+            None => {
+                writeln!(writer, "ad_statistic{}", &delim)?;
+            }
+        }
+    }
+
+    writer.flush()
+}
+
+/// A histogram of the input along with what else `histogram` reports about its values.
+struct Binned {
+    histo: Histogram,
+    stats: [(&'static str, f64); 5],
+    #[cfg(feature = "template")]
+    num_bins: usize,
+    total: usize,
+    /// How many `-inf` and `inf` values went to their own rows.
+    infinite: (usize, usize),
+    exceedances: Vec<usize>,
+    /// The exact value of each `--exact-quantiles` quantile.
+    quantiles: Vec<f64>,
+    normality: Option<Option<(f64, f64)>>,
+    samples: Option<Vec<Vec<String>>>,
+    /// Why each broken `--assert` failed.
+    broken: Vec<String>,
+}
+
+fn bin_values(
+    values: Vec<f64>,
+    rows: Vec<String>,
+    infinity_bin: bool,
+    args: &HistogramArgs,
+) -> Binned {
+    let exceedances: Vec<usize> = args
+        .thresholds
+        .iter()
+        .map(|&threshold| values.iter().filter(|&&value| value > threshold).count())
+        .collect();
+    let total = values.len();
+    let normality =
+        (args.test == Some(Test::Normality)).then(|| analysis::anderson_darling(&values));
+
+    let (lower, upper) = clamp_edges(args, finite_range(&values));
+    let policy = NonFinitePolicy::from(args.nan);
+    let mut infinite = (0, 0);
+    let mut counted = (Vec::with_capacity(values.len()), Vec::new());
+    let mut rows = rows.into_iter();
+    for (i, value) in values.into_iter().enumerate() {
+        let row = rows.next();
+        if infinity_bin && value.is_infinite() {
+            count_infinity(&mut infinite, value);
+        } else if let Some(value) = policy
+            .apply_one(i, value, lower, upper)
+            .unwrap_or_else(|err| fail(err))
+        {
+            counted.0.push(value);
+            counted.1.extend(row);
+        }
+    }
+    let (values, rows) = counted;
+
+    let stats = summary(&values);
+    let quantiles: Vec<f64> = args
+        .exact_quantiles
+        .iter()
+        .map(|&q| analysis::quantile(&values, q).unwrap_or(f64::NAN))
+        .collect();
+    let num_bins = num_bins(args, &values);
+    let broken = args
+        .assertions
+        .iter()
+        .filter_map(|assertion| assertion.check(&values))
+        .collect();
+
+    let kept = args.keep_samples.map(|k| (k, values.clone(), rows));
+    let histo = if let Some((lower, upper)) = args.range {
+        Histogram::with_range(&values, num_bins, lower, upper, args.out_of_range.into())
+            .unwrap_or_else(|err| fail(err))
+    } else if args.discrete {
+        Histogram::discrete(&values)
+    } else if args.log_bins {
+        Histogram::log_spaced(&values, num_bins).unwrap_or_else(|err| fail(err))
+    } else if let Some(edges) = &args.edges {
+        Histogram::with_edges(&values, edges).unwrap_or_else(|err| fail(err))
+    } else if let Some(significant_digits) = args.hdr {
+        let binning = Exponential {
+            base: args.hdr_base,
+            significant_digits,
+        };
+        Histogram::binned(&values, &binning).unwrap_or_else(|err| fail(err))
+    } else if let Some(width) = args.bin_width {
+        Histogram::with_bin_width(&values, width, args.origin).unwrap_or_else(|err| fail(err))
+    } else {
+        Histogram::from_values(values, num_bins).unwrap_or_else(|err| fail(err))
+    };
+    let histo = if args.cumulative {
+        histo.cumulative()
+    } else {
+        histo
+    };
+    let samples = kept.map(|(k, values, rows)| sample_rows(&histo, &values, rows, k));
+
+    Binned {
+        histo,
+        stats,
+        #[cfg(feature = "template")]
+        num_bins,
+        total,
+        infinite,
+        exceedances,
+        quantiles,
+        normality,
+        samples,
+        broken,
+    }
+}
+
+/// Bins values weighted by `--weight-column`, each adding its row's weight to its bin and
+/// counting as much in the summary statistics and `--assert`ions.
+fn bin_weighted(input: &InputArgs, weight_column: u32, args: &HistogramArgs) -> Binned {
+    let column = input
+        .column
+        .expect("clap requires column for weight_column");
+    let value = column_value(column, input);
+    let weights = ColumnParser::<String>::single(weight_column, &input.delim);
+    let mut pairs = Vec::new();
+    for_each_row(
+        input,
+        |row| Ok((value(row)?, parse_weight(&weights.parse_row(row)?[0])?)),
+        |pair, _| pairs.push(pair),
+    );
+    let total = pairs.len();
+
+    let values: Vec<f64> = pairs.iter().map(|&(value, _)| value).collect();
+    let (lower, upper) = clamp_edges(args, finite_range(&values));
+    let policy = NonFinitePolicy::from(args.nan);
+    let mut counted = Vec::with_capacity(pairs.len());
+    for (i, (value, weight)) in pairs.into_iter().enumerate() {
+        if let Some(value) = policy
+            .apply_one(i, value, lower, upper)
+            .unwrap_or_else(|err| fail(err))
+        {
+            counted.push((value, weight));
+        }
+    }
+
+    let histo = match args.range {
+        Some((lower, upper)) => {
+            let mut builder = HistogramBuilder::new(lower, upper, args.num_bins)
+                .unwrap_or_else(|err| fail(err))
+                .out_of_range(args.out_of_range.into());
+            for &(value, weight) in &counted {
+                builder.push_weighted(value, weight);
+            }
+            builder.finish()
         }
         None => {
-            let expr = args
-                .expr
-                .expect("clap arg groups enforce expr must be Some");
-
-            let re = Regex::new(r"\?([0-9]*)").unwrap();
-            let columns: Vec<u32> = re
-                .captures_iter(&expr)
-                .map(|c| c.extract())
-                .map(|(_, [col])| col.parse::<u32>().unwrap())
-                .collect();
+            Histogram::from_weighted_values(&counted, args.num_bins).unwrap_or_else(|err| fail(err))
+        }
+    };
+    let histo = if args.cumulative {
+        histo.cumulative()
+    } else {
+        histo
+    };
 
-            let expr_repl = expr.replace("?", "_");
-            let vars: Vec<String> = columns.iter().map(|col| format!("_{}", col)).collect();
+    Binned {
+        histo,
+        stats: stats_summary(Stats::from_weighted_values(&counted).as_ref()),
+        #[cfg(feature = "template")]
+        num_bins: args.num_bins,
+        total,
+        infinite: (0, 0),
+        exceedances: Vec::new(),
+        quantiles: Vec::new(),
+        normality: None,
+        samples: None,
+        broken: args
+            .assertions
+            .iter()
+            .filter_map(|assertion| assertion.check_weighted(&counted))
+            .collect(),
+    }
+}
 
-            let parser = ColumnParser::<f64>::new(&columns[..], &args.delim);
-            reader
-                .lines()
-                .map(|row| row.unwrap())
-                .map(|row| parser.parse_row(&row).unwrap())
-                .map(|vals| {
-                    let mut ctx = Context::new();
-                    for (var, val) in vars.iter().zip(vals.into_iter()) {
-                        ctx.var(var, val);
+/// Adds the infinite `value` to the count of `-inf` or `inf` values in `infinite`.
+fn count_infinity(infinite: &mut (usize, usize), value: f64) {
+    match value.is_sign_negative() {
+        true => infinite.0 += 1,
+        false => infinite.1 += 1,
+    }
+}
+
+/// Bins values `--memory-limit` moved to a spill file in a second pass over it, on the range
+/// seen while spilling them unless `--range` is given.
+fn bin_spilled(mut spilled: Spilled, infinity_bin: bool, args: &HistogramArgs) -> Binned {
+    let (range, count) = (spilled.range, spilled.count);
+    bin_in_range(range, count, infinity_bin, args, |push| {
+        spilled
+            .values()
+            .expect("failed to read spill file")
+            .for_each(push)
+    })
+}
+
+/// Bins the input in a single pass on the range and count a `--cache` kept from an earlier
+/// run, with no spill file and no more than a chunk of the values held at once.
+fn bin_cached(
+    input: &InputArgs,
+    range: (f64, f64),
+    count: usize,
+    infinity_bin: bool,
+    args: &HistogramArgs,
+) -> Binned {
+    bin_in_range(Some(range), count, infinity_bin, args, |push| {
+        let mut rest = parse_values(input, None, |values| values.drain(..).for_each(&mut *push));
+        rest.drain(..).for_each(push)
+    })
+}
+
+/// Bins the values `each` pushes on `--range`, or else `range`, of `count` values found before
+/// binning, which the values must fall in.
+fn bin_in_range(
+    range: Option<(f64, f64)>,
+    count: usize,
+    infinity_bin: bool,
+    args: &HistogramArgs,
+    each: impl FnOnce(&mut dyn FnMut(f64)),
+) -> Binned {
+    let mut exceedances = vec![0; args.thresholds.len()];
+    let (mut total, mut infinite) = (0, (0, 0));
+    let limit = args
+        .memory_limit
+        .expect("only --memory-limit bins in a range");
+    let mut sort = (!args.exact_quantiles.is_empty())
+        .then(|| ExternalSort::new(&tmpdir(args), (limit / BYTES_PER_VALUE).max(1) as usize));
+    let histo = match args.range.or(range) {
+        None => fail(rhisto::Error::NoValues),
+        Some(_) if args.num_bins == 0 => fail(rhisto::Error::NoBins),
+        Some((lower, upper)) if lower == upper => fail(match count {
+            1 => rhisto::Error::SingleValue(lower),
+            _ => rhisto::Error::IdenticalValues(lower),
+        }),
+        Some((lower, upper)) => {
+            let mut builder =
+                HistogramBuilder::new(lower, upper, args.num_bins).unwrap_or_else(|err| fail(err));
+            if args.range.is_some() {
+                builder = builder.out_of_range(args.out_of_range.into());
+            }
+            let policy = NonFinitePolicy::from(args.nan);
+            each(&mut |value| {
+                for (threshold, count) in args.thresholds.iter().zip(&mut exceedances) {
+                    if value > *threshold {
+                        *count += 1;
                     }
+                }
+                if infinity_bin && value.is_infinite() {
+                    count_infinity(&mut infinite, value);
+                } else if let Some(value) = policy
+                    .apply_one(total, value, lower, upper)
+                    .unwrap_or_else(|err| fail(err))
+                {
+                    builder.push(value);
+                    if let Some(sort) = &mut sort {
+                        sort.push(value).expect("failed to write sort file");
+                    }
+                }
+                total += 1;
+            });
+            builder.finish()
+        }
+    };
+    let quantiles = match sort {
+        Some(sort) => sort
+            .quantiles(&args.exact_quantiles)
+            .expect("failed to read sort file"),
+        None => Vec::new(),
+    };
+    let histo = if args.cumulative {
+        histo.cumulative()
+    } else {
+        histo
+    };
 
-                    meval::eval_str_with_context(&expr_repl, &ctx).unwrap()
-                })
+    let stats = stats_summary(histo.stats());
+    Binned {
+        histo,
+        stats,
+        #[cfg(feature = "template")]
+        num_bins: args.num_bins,
+        total,
+        infinite,
+        exceedances,
+        quantiles,
+        normality: None,
+        samples: None,
+        broken: Vec::new(),
+    }
+}
+
+/// Bins the values in one pass through a `--tdigest` sketch, holding no more than a chunk of
+/// them at once.
+fn bin_digest(
+    input: &InputArgs,
+    compression: f64,
+    infinity_bin: bool,
+    args: &HistogramArgs,
+) -> Binned {
+    let mut digest = TDigest::new(compression).unwrap_or_else(|err| fail(err));
+    let mut stats: Option<Stats> = None;
+    let mut exceedances = vec![0; args.thresholds.len()];
+    let (mut total, mut infinite) = (0, (0, 0));
+    let policy = NonFinitePolicy::from(args.nan);
+    let mut feed = |values: &mut Vec<f64>| {
+        let mut counted = Vec::with_capacity(values.len());
+        for value in values.drain(..) {
+            for (threshold, count) in args.thresholds.iter().zip(&mut exceedances) {
+                if value > *threshold {
+                    *count += 1;
+                }
+            }
+            if infinity_bin && value.is_infinite() {
+                count_infinity(&mut infinite, value);
+            } else if let Some(value) = policy
+                .apply_one(total, value, f64::NAN, f64::NAN)
+                .unwrap_or_else(|err| fail(err))
+                .filter(|value| value.is_finite())
+            {
+                digest.push(value);
+                counted.push(value);
+            }
+            total += 1;
+        }
+        if let Some(chunk) = Stats::from_values(&counted) {
+            stats = Some(
+                stats
+                    .as_ref()
+                    .map_or(chunk.clone(), |stats| stats.merge(&chunk)),
+            );
+        }
+    };
+    let mut rest = parse_values(input, None, &mut feed);
+    feed(&mut rest);
+
+    let histo = digest
+        .histogram(args.num_bins)
+        .unwrap_or_else(|err| fail(err));
+    let histo = if args.cumulative {
+        histo.cumulative()
+    } else {
+        histo
+    };
+    Binned {
+        histo,
+        stats: stats_summary(stats.as_ref()),
+        #[cfg(feature = "template")]
+        num_bins: args.num_bins,
+        total,
+        infinite,
+        exceedances,
+        quantiles: Vec::new(),
+        normality: None,
+        samples: None,
+        broken: Vec::new(),
+    }
+}
+
+/// Reads a saved histogram or schema, failing with the path if it cannot be read or parsed.
+fn read_parsed<T: std::str::FromStr>(path: &Path) -> T
+where
+    T::Err: std::fmt::Display,
+{
+    std::fs::read_to_string(path)
+        .unwrap_or_else(|err| fail(format!("failed to read `{}`: {}", path.display(), err)))
+        .parse()
+        .unwrap_or_else(|err| fail(format!("{}: {}", path.display(), err)))
+}
+
+/// The counts `overlay` expects of each of `histo`'s bins, out of as many values as it holds.
+fn overlay_counts(overlay: &Overlay, histo: &Histogram, stats: [(&str, f64); 5]) -> Vec<f64> {
+    let total: f64 = histo.iter().map(|bin| bin.count).sum();
+    match overlay {
+        Overlay::Normal => {
+            let (mean, stddev) = (stats[1].1, stats[2].1);
+            histo
+                .iter()
+                .map(|bin| total * analysis::normal_mass(bin.lower, bin.upper, mean, stddev))
+                .collect()
+        }
+        Overlay::Uniform => {
+            let edges = histo.edges();
+            let span = edges[edges.len() - 1] - edges[0];
+            histo
+                .iter()
+                .map(|bin| total * (bin.upper - bin.lower) / span)
+                .collect()
+        }
+        Overlay::Histogram(path) => {
+            let reference = read_parsed::<Rhst>(path).histogram;
+            if reference.edges() != histo.edges() {
+                fail(rhisto::Error::MismatchedEdges(
+                    histo.edges().to_vec(),
+                    reference.edges().to_vec(),
+                ));
+            }
+            let reference_total: f64 = reference.iter().map(|bin| bin.count).sum();
+            if reference_total == 0.0 {
+                fail(format!("{} has no values", path.display()));
+            }
+            reference
+                .iter()
+                .map(|bin| bin.count * total / reference_total)
                 .collect()
         }
+    }
+}
+
+/// `stats` as the pairs [`summary`] gives, or those of no values.
+fn stats_summary(stats: Option<&Stats>) -> [(&'static str, f64); 5] {
+    match stats {
+        Some(stats) => [
+            ("count", stats.count),
+            ("mean", stats.mean),
+            ("stddev", stats.stddev),
+            ("min", stats.min),
+            ("max", stats.max),
+        ],
+        None => summary(&[]),
+    }
+}
+
+/// Ends the run over input that cannot be used, e.g. values that cannot be binned, with why
+/// on stderr.
+fn fail(err: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", err);
+    std::process::exit(1);
+}
+
+/// The lowest and highest finite values, if there are any.
+fn finite_range(values: &[f64]) -> Option<(f64, f64)> {
+    values
+        .iter()
+        .filter(|value| value.is_finite())
+        .fold(None, |acc, &value| match acc {
+            Some((min, max)) => Some((f64::min(min, value), f64::max(max, value))),
+            None => Some((value, value)),
+        })
+}
+
+/// Where `--nan clamp-to-edges` counts infinities: at the ends of `--range` or `--edges`, or
+/// otherwise of the finite values' `range`.
+fn clamp_edges(args: &HistogramArgs, range: Option<(f64, f64)>) -> (f64, f64) {
+    let edges = args.edges.as_deref().unwrap_or_default();
+    match (args.range, edges.first(), edges.last()) {
+        (Some(range), _, _) => range,
+        (None, Some(&lower), Some(&upper)) => (lower, upper),
+        _ => range.unwrap_or((f64::NAN, f64::NAN)),
+    }
+}
+
+/// `--num-bins`, or the number of bins `--auto-bins` picks for `values`.
+fn num_bins(args: &HistogramArgs, values: &[f64]) -> usize {
+    args.auto_bins.map_or(args.num_bins, |rule| {
+        rhisto::AutoBins::from(rule).num_bins(values)
+    })
+}
+
+/// The `--bin-labels` name of the `i`th bin, or its `label` formatted by [`format_label`].
+fn bin_label(i: usize, label: f64, args: &HistogramArgs) -> String {
+    match args.bin_labels.as_ref().and_then(|names| names.get(i)) {
+        Some(name) => name.to_string(),
+        None => format_label(label, args),
+    }
+}
+
+/// Formats a text bin label as `--label-units` or `--sig-figs` ask.
+fn format_label(label: f64, args: &HistogramArgs) -> String {
+    match (args.label_units, args.sig_figs) {
+        (Some(units), _) => units.format(label),
+        (None, Some(sig_figs)) => format_sig_figs(label, sig_figs),
+        (None, None) => format!("{:0.2}", label),
+    }
+}
+
+/// Writes a kernel density estimate of the input at `points` evenly spaced values.
+fn kde(
+    out: &mut dyn Write,
+    input: InputArgs,
+    args: HistogramArgs,
+    points: usize,
+) -> std::io::Result<()> {
+    let delim = input.delim.clone();
+    let values = read_values(input);
+    let kde = match args.bandwidth {
+        Some(bandwidth) => Kde::with_bandwidth(&values, bandwidth),
+        None => Kde::new(&values),
+    }
+    .unwrap_or_else(|err| fail(err));
+
+    let mut writer: Box<dyn Write + '_> = match &args.output {
+        Some(path_buf) => Box::new(BufWriter::new(
+            File::create(path_buf).expect("failed to open output file"),
+        )),
+        None => Box::new(out),
+    };
+    for (x, density) in kde.grid(points) {
+        writeln!(
+            writer,
+            "{}{}{}",
+            format_label(x, &args),
+            &delim,
+            format_sig_figs(density, 4)
+        )?;
+    }
+
+    writer.flush()
+}
+
+/// Reads each `--group-by-file` input as a group named for its file, written by
+/// [`write_groups`].
+fn group_by_file(
+    out: &mut dyn Write,
+    input: InputArgs,
+    args: HistogramArgs,
+) -> std::io::Result<()> {
+    check_group_format(&args, "--group-by-file");
+    let names = group_names(&args.group_by_file);
+    let groups: Vec<(String, Vec<f64>)> = names
+        .into_iter()
+        .zip(&args.group_by_file)
+        .map(|(name, path)| {
+            let mut input = input.clone();
+            input.input = Some(path.clone());
+            (name, read_values(input))
+        })
+        .collect();
+    write_groups(out, &input, args, None, "--group-by-file", groups)
+}
+
+/// Fails unless `--format-out` is one that `flag`'s groups can be written as, before any input
+/// is read.
+fn check_group_format(args: &HistogramArgs, flag: &str) {
+    if !matches!(args.format_out, Format::Text | Format::Rhst | Format::Json) {
+        let format = args
+            .format_out
+            .to_possible_value()
+            .expect("formats are not hidden");
+        fail(format!(
+            "`{}` writes text, rhst or json output, not {}",
+            flag,
+            format.get_name()
+        ));
+    }
+}
+
+/// Reads the input once, splitting its values into groups by their rows' value in `column`,
+/// for `--group-by` and `compare --group-by`.
+fn read_groups(input: &InputArgs, column: u32) -> BTreeMap<String, Vec<f64>> {
+    let extract = extractor(input);
+    let keys = ColumnParser::<String>::single(column, &input.delim);
+    let mut groups: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    for_each_row(
+        input,
+        |row| Ok((keys.parse_row(row)?.remove(0), extract(row)?)),
+        |(key, (value, count)), _| {
+            groups
+                .entry(key)
+                .or_default()
+                .extend(std::iter::repeat_n(value, count))
+        },
+    );
+    groups
+}
+
+/// Bins the named `groups` read from `source` on the same edges, writing them as the groups of
+/// one `.rhst`, or as text with a column of counts per group under a header row naming them.
+fn write_groups(
+    out: &mut dyn Write,
+    input: &InputArgs,
+    args: HistogramArgs,
+    source: Option<PathBuf>,
+    flag: &str,
+    groups: impl IntoIterator<Item = (String, Vec<f64>)>,
+) -> std::io::Result<()> {
+    check_group_format(&args, flag);
+    let (names, groups): (Vec<String>, Vec<Vec<f64>>) = groups.into_iter().unzip();
+    let all: Vec<f64> = groups.iter().flatten().copied().collect();
+    if all.is_empty() {
+        fail(format!("there are no values in the `{}` groups", flag));
+    }
+    let num_bins = num_bins(&args, &all);
+    let edges = match args.bin_width {
+        Some(width) => Histogram::with_bin_width(&all, width, args.origin)
+            .unwrap_or_else(|err| fail(err))
+            .edges()
+            .to_vec(),
+        None => args.edges.as_deref().unwrap_or_default().to_vec(),
+    };
+
+    let bin = |values: &[f64]| {
+        if !edges.is_empty() {
+            Histogram::with_edges(values, &edges).unwrap_or_else(|err| fail(err))
+        } else {
+            let (lower, upper) = args.range.unwrap_or_else(|| {
+                all.iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), &v| {
+                        (min.min(v), max.max(v))
+                    })
+            });
+            if lower == upper {
+                fail(match all.len() {
+                    1 => rhisto::Error::SingleValue(lower),
+                    _ => rhisto::Error::IdenticalValues(lower),
+                });
+            }
+            Histogram::with_range(values, num_bins, lower, upper, args.out_of_range.into())
+                .unwrap_or_else(|err| fail(err))
+        }
+    };
+    let total = bin(&all);
+    let histos: Vec<Histogram> = groups.iter().map(|values| bin(values)).collect();
+
+    let mut writer: Box<dyn Write + '_> = match &args.output {
+        Some(path_buf) => Box::new(BufWriter::new(
+            File::create(path_buf).expect("failed to open output file"),
+        )),
+        None => Box::new(out),
     };
 
-    let histo = Histogram::from_values(values, args.num_bins);
+    match args.format_out {
+        Format::Rhst => {
+            let provenance = (!args.no_metadata).then(|| Provenance::new(source, all.len()));
+            let mut rhst = to_rhst(total, summary(&all), provenance);
+            rhst.groups = names.into_iter().zip(histos).collect();
+            write!(writer, "{}", rhst)?;
+        }
+        Format::Json => {
+            let provenance = (!args.no_metadata).then(|| Provenance::new(source, all.len()));
+            write_json_bundle(
+                &mut writer,
+                total,
+                names.into_iter().zip(histos).collect(),
+                args.bin_labels.as_ref(),
+                provenance.as_ref(),
+            )?;
+        }
+        Format::Text => {
+            let delim = &input.delim;
+            writeln!(writer, "bin{}{}", delim, names.join(delim))?;
+            let grand_total: f64 = histos.iter().flatten().map(|bin| bin.count).sum();
+            let columns: Vec<Vec<String>> = histos
+                .iter()
+                .map(|histo| {
+                    if let Some(normalize) = args.normalize {
+                        let scaled = match normalize {
+                            Normalize::All => {
+                                histo.iter().map(|bin| bin.count / grand_total).collect()
+                            }
+                            Normalize::WithinGroup => histo.normalized(),
+                        };
+                        scaled
+                            .iter()
+                            .map(|value| format!("{:0.4}", value))
+                            .collect()
+                    } else if args.density {
+                        let scaled = histo.density();
+                        scaled
+                            .iter()
+                            .map(|value| format!("{:0.4}", value))
+                            .collect()
+                    } else {
+                        histo
+                            .clone()
+                            .into_counts()
+                            .iter()
+                            .map(f64::to_string)
+                            .collect()
+                    }
+                })
+                .collect();
+            for (i, label) in total.into_labels().into_iter().enumerate() {
+                write!(writer, "{}", bin_label(i, label, &args))?;
+                for column in &columns {
+                    write!(writer, "{}{}", delim, column[i])?;
+                }
+                writeln!(writer)?;
+            }
+        }
+        _ => unreachable!("other formats are refused before reading"),
+    }
+
+    writer.flush()
+}
+
+/// The file names of `paths`, or the whole paths when two of them share a file name.
+fn group_names(paths: &[PathBuf]) -> Vec<String> {
+    let names: Vec<String> = paths
+        .iter()
+        .map(|path| {
+            path.file_name()
+                .unwrap_or(path.as_os_str())
+                .to_string_lossy()
+                .into_owned()
+        })
+        .collect();
+    let unique: std::collections::HashSet<&String> = names.iter().collect();
+    if unique.len() == names.len() {
+        return names;
+    }
+
+    paths
+        .iter()
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect()
+}
+
+/// Tallies the text of `--column` and writes one `key<delim>count` row per distinct key, most
+/// frequent first.
+fn categorical(out: &mut dyn Write, input: InputArgs, args: HistogramArgs) -> std::io::Result<()> {
+    let column = input.column.expect("clap requires column for categorical");
+    let key = column_text(column, &input);
+    let counts = input
+        .count_column
+        .map(|column| ColumnParser::<usize>::single(column, &input.delim));
+    let mut histo = CategoricalHistogram::default();
+    for_each_row(
+        &input,
+        |row| {
+            let count = match &counts {
+                Some(counts) => counts.parse_row(row)?[0],
+                None => 1,
+            };
+            Ok((key(row)?, count))
+        },
+        |(key, count), _| histo.push_weighted(&key, count as f64),
+    );
 
-    let mut writer: Box<dyn Write> = match args.output {
+    let mut writer: Box<dyn Write + '_> = match args.output {
         Some(path_buf) => Box::new(BufWriter::new(
             File::create(&path_buf).expect("failed to open output file"),
         )),
-        None => Box::new(BufWriter::new(std::io::stdout())),
+        None => Box::new(out),
     };
 
-    for bin in histo.into_bins().iter() {
-        let _ = writeln!(writer, "{:0.2}{}{:0.2}", bin.label, &args.delim, bin.count);
+    let total = histo.total();
+    for (key, count) in histo.into_sorted() {
+        if args.normalize.is_some() {
+            writeln!(writer, "{}{}{:0.4}", key, &input.delim, count / total)?;
+        } else {
+            writeln!(writer, "{}{}{}", key, &input.delim, count)?;
+        }
     }
+
+    writer.flush()
 }
 
-#[derive(Parser, Debug)]
-#[command(version, about, long_about = None)]
-struct Args {
-    /// The optional buffer to read data from.
-    input: Option<PathBuf>,
+fn to_rhst(
+    histo: Histogram,
+    stats: [(&'static str, f64); 5],
+    provenance: Option<Provenance>,
+) -> Rhst {
+    let mut rhst = Rhst::new(histo);
+    rhst.stats = stats
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    for (key, value) in provenance.iter().flat_map(|provenance| provenance.pairs()) {
+        rhst.metadata.insert(key.to_string(), value);
+    }
 
-    /// The optional path to write histogram data to.
-    #[arg(short, long)]
-    output: Option<PathBuf>,
+    rhst
+}
 
-    /// The zero indexed column in the input buffer to read.
-    #[arg(short, long, group = "value")]
-    column: Option<u32>,
+fn write_literals(
+    writer: &mut dyn Write,
+    edges: &[f64],
+    counts: &[String],
+    format: Format,
+    provenance: Option<&Provenance>,
+) -> std::io::Result<()> {
+    let comment = if format == Format::Rust { "//" } else { "#" };
+    for (key, value) in provenance.iter().flat_map(|provenance| provenance.pairs()) {
+        writeln!(writer, "{} {}: {}", comment, key, value)?;
+    }
 
-    /// The expression over column indices used to compute histogram values.
-    ///
-    /// The `?` prefixes a column index in an expression.
-    /// The expression is evaluated on each row.
-    /// Any `?`ed column indices are bound to a concrete value for each row.
-    #[arg(short, long, group = "value")]
-    expr: Option<String>,
+    let list = |items: Vec<String>| items.join(", ");
+    let edges = list(edges.iter().map(|edge| format!("{:?}", edge)).collect());
+    let counts = list(counts.to_vec());
 
-    /// The delimeting pattern used to separate columns in the input.
-    #[arg(short, long, default_value = ",")]
-    delim: String,
+    match format {
+        Format::Rust => writeln!(
+            writer,
+            "let edges = [{}];\nlet counts = [{}];",
+            edges, counts
+        ),
+        Format::Python => writeln!(writer, "edges = [{}]\ncounts = [{}]", edges, counts),
+        Format::Numpy => writeln!(
+            writer,
+            "edges = np.array([{}])\ncounts = np.array([{}])",
+            edges, counts
+        ),
+        _ => unreachable!("only literal formats are written as text"),
+    }
+}
 
-    /// Indicate whether the input data contains a header row.
-    #[arg(short, long, default_value_t = false)]
-    skip_header: bool,
+/// Picks up to `k` of the `rows` that `values` were read from for each bin, uniformly at random
+/// by reservoir sampling.
+fn sample_rows(histo: &Histogram, values: &[f64], rows: Vec<String>, k: usize) -> Vec<Vec<String>> {
+    let num_bins = histo.edges().len().saturating_sub(1);
+    let mut samples = vec![Vec::new(); num_bins];
+    let mut seen = vec![0; num_bins];
+    // A fixed seed, so the same input keeps the same samples from run to run.
+    let mut rng = XorShift(0x9e37_79b9_7f4a_7c15);
+    for (value, row) in values.iter().zip(rows) {
+        let Some(i) = histo.bin_index(*value) else {
+            continue;
+        };
 
-    /// The number of bins in the histogram.
-    #[arg(short, long, default_value_t = 10)]
-    num_bins: usize,
+        seen[i] += 1;
+        if samples[i].len() < k {
+            samples[i].push(row);
+        } else {
+            let j = rng.below(seen[i]);
+            if j < k {
+                samples[i][j] = row;
+            }
+        }
+    }
+
+    samples
+}
+
+/// Writes `rows` values drawn from `histo`, each from a bin picked in proportion to its count
+/// and uniformly within it.
+fn sample(out: &mut dyn Write, histo: &Histogram, rows: usize, seed: u64) -> std::io::Result<()> {
+    let mut below = 0.0;
+    let cumulative: Vec<f64> = histo
+        .iter()
+        .map(|bin| {
+            if bin.count < 0.0 {
+                fail(format!("bin {} has a negative count", bin.label));
+            }
+            below += bin.count;
+            below
+        })
+        .collect();
+    if below == 0.0 {
+        fail(rhisto::Error::NoValues);
+    }
+
+    // Xorshift never leaves zero, so that seed is nudged off it.
+    let mut rng = XorShift(seed.max(1));
+    let bins = histo.bins();
+    for _ in 0..rows {
+        let target = rng.unit() * below;
+        let i = cumulative
+            .partition_point(|&count| count <= target)
+            .min(bins.len() - 1);
+        let bin = &bins[i];
+        writeln!(out, "{}", bin.lower + rng.unit() * (bin.upper - bin.lower))?;
+    }
+
+    Ok(())
+}
+
+/// A xorshift64 generator, plenty for picking samples.
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value in `0..n`, with negligible bias for the `n` used here.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// A value in `[0, 1)`, from the top 53 bits.
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+fn write_json(
+    writer: &mut dyn Write,
+    histo: Histogram,
+    names: Option<&BinLabels>,
+    samples: Option<&[Vec<String>]>,
+    provenance: Option<&Provenance>,
+) -> std::io::Result<()> {
+    let mut fields = json_histogram_fields(histo, names, samples);
+    fields.extend(provenance.map(json_metadata));
+    writeln!(writer, "{{{}}}", fields.join(","))
+}
+
+/// Writes the `--group-by-file` or `--group-by` histograms as one JSON object: the `total`
+/// histogram of every group, a `groups` array with each group's histogram under its `name`,
+/// and any `metadata`.
+fn write_json_bundle(
+    writer: &mut dyn Write,
+    total: Histogram,
+    groups: Vec<(String, Histogram)>,
+    names: Option<&BinLabels>,
+    provenance: Option<&Provenance>,
+) -> std::io::Result<()> {
+    let groups: Vec<String> = groups
+        .into_iter()
+        .map(|(name, histo)| {
+            let mut fields = vec![format!("\"name\":{}", json_string(&name))];
+            fields.extend(json_histogram_fields(histo, names, None));
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+
+    let mut fields = vec![
+        format!(
+            "\"total\":{{{}}}",
+            json_histogram_fields(total, names, None).join(",")
+        ),
+        format!("\"groups\":[{}]", groups.join(",")),
+    ];
+    fields.extend(provenance.map(json_metadata));
+    writeln!(writer, "{{{}}}", fields.join(","))
+}
+
+/// The `bins`, `underflow` and `overflow` fields of a JSON histogram object.
+fn json_histogram_fields(
+    histo: Histogram,
+    names: Option<&BinLabels>,
+    samples: Option<&[Vec<String>]>,
+) -> Vec<String> {
+    let (underflow, overflow) = (histo.underflow(), histo.overflow());
+    let bins: Vec<String> = histo
+        .into_bins()
+        .into_iter()
+        .enumerate()
+        .map(|(i, bin)| {
+            let mut fields = vec![
+                format!("\"label\":{}", json_number(bin.label)),
+                format!("\"lower\":{}", json_number(bin.lower)),
+                format!("\"upper\":{}", json_number(bin.upper)),
+                format!("\"count\":{}", bin.count),
+            ];
+            if let Some(name) = names.and_then(|names| names.get(i)) {
+                fields.push(format!("\"name\":{}", json_string(name)));
+            }
+            if let Some(samples) = samples {
+                let rows: Vec<String> = samples[i].iter().map(|row| json_string(row)).collect();
+                fields.push(format!("\"samples\":[{}]", rows.join(",")));
+            }
+            format!("{{{}}}", fields.join(","))
+        })
+        .collect();
+
+    vec![
+        format!("\"bins\":[{}]", bins.join(",")),
+        format!("\"underflow\":{}", underflow),
+        format!("\"overflow\":{}", overflow),
+    ]
+}
+
+/// The `metadata` field of a JSON object, holding the provenance pairs.
+fn json_metadata(provenance: &Provenance) -> String {
+    let pairs: Vec<String> = provenance
+        .pairs()
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key), json_string(value)))
+        .collect();
+    format!("\"metadata\":{{{}}}", pairs.join(","))
+}
+
+/// `histo` in the Prometheus text exposition format, as cumulative `le` buckets with a
+/// `_sum` and `_count` named after `metric`.
+///
+/// The buckets and count include the values outside the edges, but the sum only has the
+/// values that were counted in a bin.
+fn prometheus_histogram(metric: &str, histo: &Histogram) -> String {
+    let edges = histo.edges();
+    let mut text = format!("# TYPE {} histogram\n", metric);
+    let mut cumulative = histo.underflow();
+    for (bin, upper) in histo.iter().zip(edges.iter().skip(1)) {
+        cumulative += bin.count;
+        text.push_str(&format!(
+            "{}_bucket{{le=\"{}\"}} {}\n",
+            metric, upper, cumulative
+        ));
+    }
+
+    let count = cumulative + histo.overflow();
+    let sum = match histo.stats() {
+        Some(stats) => stats.mean * stats.count,
+        None => histo.iter().map(|bin| bin.label * bin.count).sum(),
+    };
+    text.push_str(&format!("{}_bucket{{le=\"+Inf\"}} {}\n", metric, count));
+    text.push_str(&format!("{}_sum {}\n", metric, sum));
+    text.push_str(&format!("{}_count {}\n", metric, count));
+    text
+}
+
+/// Connects to the first of `address`'s resolved addresses that answers within
+/// [`PUSH_TIMEOUT`], with reads and writes timing out after as long, so a `--push` to an
+/// unreachable gateway fails instead of hanging the run.
+fn connect(address: &str) -> std::io::Result<std::net::TcpStream> {
+    use std::net::ToSocketAddrs;
+
+    let mut last = None;
+    for address in address.to_socket_addrs()? {
+        match std::net::TcpStream::connect_timeout(&address, PUSH_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(PUSH_TIMEOUT))?;
+                stream.set_write_timeout(Some(PUSH_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(err) => last = Some(err),
+        }
+    }
+
+    Err(last.unwrap_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("{} has no addresses", address),
+        )
+    }))
+}
+
+/// PUTs `body` to a Pushgateway at the plain `http://` `url`, replacing the metrics of its
+/// job and grouping labels.
+fn push(url: &str, body: &str) -> std::io::Result<()> {
+    let invalid = |reason: &str| std::io::Error::new(std::io::ErrorKind::InvalidInput, reason);
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| invalid("--push needs an http:// URL"))?;
+    let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let address = match host.contains(':') {
+        true => host.to_string(),
+        false => format!("{}:80", host),
+    };
+
+    let mut stream = connect(&address)?;
+    write!(
+        stream,
+        "PUT {} HTTP/1.1\r\nHost: {}\r\nContent-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+        if path.is_empty() { "/" } else { path },
+        host,
+        body.len(),
+        body
+    )?;
+
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(std::io::Error::other(format!(
+            "the push was refused: {}",
+            status.trim_end()
+        ))),
+    }
+}
+
+/// `value` as a JSON number, or `null` for the non-finite values JSON cannot represent.
+fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        format!("{:?}", value)
+    } else {
+        "null".to_string()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+/// Where a histogram came from, embedded in outputs unless `--no-metadata` is given.
+struct Provenance {
+    input: String,
+    command: String,
+    rows: usize,
+    timestamp: u64,
+}
+
+impl Provenance {
+    fn new(input: Option<PathBuf>, rows: usize) -> Self {
+        Self {
+            input: input.map_or("-".to_string(), |path| path.display().to_string()),
+            command: std::env::args().collect::<Vec<_>>().join(" "),
+            rows,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+        }
+    }
+
+    fn pairs(&self) -> [(&'static str, String); 5] {
+        [
+            ("input", self.input.clone()),
+            ("command", self.command.clone()),
+            ("rows", self.rows.to_string()),
+            ("timestamp", self.timestamp.to_string()),
+            ("version", env!("CARGO_PKG_VERSION").to_string()),
+        ]
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(
+    path: &std::path::Path,
+    histo: Histogram,
+    provenance: Option<&Provenance>,
+) -> parquet::errors::Result<()> {
+    use parquet::{
+        data_type::DoubleType,
+        file::{metadata::KeyValue, properties::WriterProperties, writer::SerializedFileWriter},
+        schema::parser::parse_message_type,
+    };
+    use std::sync::Arc;
+
+    let schema = parse_message_type(
+        "message histogram {
+            REQUIRED DOUBLE lower;
+            REQUIRED DOUBLE upper;
+            REQUIRED DOUBLE count;
+            REQUIRED DOUBLE density;
+        }",
+    )?;
+
+    let edges = histo.edges().to_vec();
+    let counts = histo.into_counts();
+    let total = counts.iter().sum::<f64>();
+    let lower = edges[..counts.len()].to_vec();
+    let upper = edges[1..].to_vec();
+    // An empty histogram has no density anywhere, rather than NaN from dividing by its total.
+    let density: Vec<f64> = counts
+        .iter()
+        .zip(lower.iter().zip(&upper))
+        .map(|(&count, (lower, upper))| match total {
+            0.0 => 0.0,
+            _ => count / (total * (upper - lower)),
+        })
+        .collect();
+
+    let file = File::create(path)?;
+    let metadata = provenance.map(|provenance| {
+        provenance
+            .pairs()
+            .into_iter()
+            .map(|(key, value)| KeyValue::new(format!("rhisto.{}", key), value))
+            .collect()
+    });
+    let props = Arc::new(
+        WriterProperties::builder()
+            .set_key_value_metadata(metadata)
+            .build(),
+    );
+    let mut writer = SerializedFileWriter::new(file, Arc::new(schema), props)?;
+    let mut row_group = writer.next_row_group()?;
+    for doubles in [&lower, &upper, &counts, &density] {
+        let mut column = row_group.next_column()?.expect("schema has four columns");
+        column
+            .typed::<DoubleType>()
+            .write_batch(doubles, None, None)?;
+        column.close()?;
+    }
+
+    row_group.close()?;
+    writer.close()?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn write_sqlite(
+    path: &std::path::Path,
+    table: &str,
+    tag: Option<&str>,
+    histo: Histogram,
+) -> rusqlite::Result<()> {
+    let table = format!("\"{}\"", table.replace('"', "\"\""));
+
+    let mut conn = rusqlite::Connection::open(path)?;
+    let tx = conn.transaction()?;
+    tx.execute(
+        &format!(
+            "CREATE TABLE IF NOT EXISTS {} (tag TEXT, lower REAL, upper REAL, count REAL)",
+            table
+        ),
+        (),
+    )?;
+
+    {
+        let mut insert = tx.prepare(&format!(
+            "INSERT INTO {} (tag, lower, upper, count) VALUES (?1, ?2, ?3, ?4)",
+            table
+        ))?;
+        for bin in histo.into_bins() {
+            insert.execute((tag, bin.lower, bin.upper, bin.count))?;
+        }
+    }
+
+    tx.commit()
+}
+
+#[cfg(feature = "template")]
+fn template_bins(histo: Histogram, names: Option<&BinLabels>) -> Vec<minijinja::Value> {
+    histo
+        .into_bins()
+        .into_iter()
+        .enumerate()
+        .map(|(i, bin)| {
+            minijinja::context! {
+                label => bin.label,
+                name => names.and_then(|names| names.get(i)),
+                lower => bin.lower,
+                upper => bin.upper,
+                count => bin.count,
+            }
+        })
+        .collect()
+}
+
+/// The count, mean, standard deviation and range of `values`, for outputs that carry them.
+fn summary(values: &[f64]) -> [(&'static str, f64); 5] {
+    let count = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / count;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count;
+    [
+        ("count", count),
+        ("mean", mean),
+        ("stddev", variance.sqrt()),
+        ("min", values.iter().copied().fold(f64::INFINITY, f64::min)),
+        (
+            "max",
+            values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        ),
+    ]
+}
+
+fn heaping(out: &mut dyn Write, values: &[f64], ratio: f64) -> std::io::Result<()> {
+    writeln!(out, "base,index")?;
+    for base in HEAPING_BASES {
+        match analysis::heaping_index(values, base) {
+            Some(index) => writeln!(out, "{},{:0.2}", base, index)?,
+            None => writeln!(out, "{},", base)?,
+        }
+    }
+
+    writeln!(out)?;
+    writeln!(out, "value,count,neighbor_mean")?;
+    // Every coarser base is a multiple of the finest, so this covers all of them.
+    for heap in analysis::heaped_values(values, HEAPING_BASES[0], ratio) {
+        writeln!(
+            out,
+            "{},{},{:0.2}",
+            heap.value, heap.count, heap.neighbor_mean
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes `stat` of the values in each `window` seconds long, by the time in `time_column`, or
+/// in each journald entry's timestamp without one.
+fn trend(
+    out: &mut dyn Write,
+    input: &InputArgs,
+    time_column: Option<u32>,
+    window: f64,
+    stat: Stat,
+) -> std::io::Result<()> {
+    let extract = extractor(input);
+    let time_of: ColumnValue = match time_column {
+        Some(column) => {
+            let times = ColumnParser::<String>::single(column, &input.delim);
+            Box::new(move |row| {
+                let time = times.parse_row(row)?.remove(0);
+                parse_time(&time).ok_or_else(|| {
+                    RowError::Failed("bad_time", format!("`{}` is not a time", time))
+                })
+            })
+        }
+        None if input.preset == Some(Preset::JournalJson) => Box::new(journal_time),
+        None => fail("`--time-column` is needed unless `--preset journal-json` is used"),
+    };
+    let mut windows: std::collections::BTreeMap<i64, Vec<f64>> = Default::default();
+    for_each_row(
+        input,
+        |row| {
+            let (value, count) = extract(row)?;
+            let time = time_of(row)?;
+            Ok(((time / window).floor() as i64, value, count))
+        },
+        |(window, value, count), _| {
+            windows
+                .entry(window)
+                .or_default()
+                .extend(std::iter::repeat_n(value, count));
+        },
+    );
+
+    writeln!(out, "window_start,{}", stat_name(stat))?;
+    for (i, values) in windows {
+        writeln!(out, "{},{}", i as f64 * window, stat.of(&values))?;
+    }
+
+    Ok(())
+}
+
+/// What `extract` prints of each row.
+struct Fields<'a> {
+    columns: &'a [u32],
+    exprs: &'a [String],
+    condition: Option<&'a Condition>,
+}
+
+/// Writes the text of `fields.columns` then the values of `fields.exprs` for each of `rows`
+/// meeting `fields.condition`, joined by `delim`.
+fn extract(
+    out: &mut dyn Write,
+    rows: impl Iterator<Item = String>,
+    fields: Fields,
+    delim: &str,
+    skip_header: bool,
+) -> std::io::Result<()> {
+    let first_line = if skip_header { 2 } else { 1 };
+    let columns = ColumnParser::<String>::new(fields.columns, delim);
+    let exprs: Vec<ColumnExpr> = fields
+        .exprs
+        .iter()
+        .map(|expr| ColumnExpr::new(expr, delim))
+        .collect();
+    let builtins = Context::new();
+    let line = |row: &str| -> Result<Option<Vec<String>>, RowError> {
+        if let Some(condition) = fields.condition
+            && !condition.holds(row, &builtins)?
+        {
+            return Ok(None);
+        }
+
+        let mut line = if fields.columns.is_empty() {
+            Vec::new()
+        } else {
+            columns.parse_row(row)?
+        };
+        for expr in &exprs {
+            line.push(expr.eval(row, &builtins)?.to_string());
+        }
+        Ok(Some(line))
+    };
+
+    for (i, row) in rows.enumerate() {
+        match line(&row) {
+            Ok(Some(line)) => writeln!(out, "{}", line.join(delim))?,
+            Ok(None) | Err(RowError::Skipped(_)) => {}
+            Err(RowError::Failed(_, message)) => {
+                out.flush()?;
+                fail(format!("line {}: {}", first_line + i, message))
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// An `--assert` bound on a statistic, e.g. `p99 < 250`.
+#[derive(Clone, Debug)]
+struct Assertion {
+    text: String,
+    stat: Stat,
+    test: Comparison,
+    bound: f64,
+}
+
+impl Assertion {
+    /// Why `values` break the assertion, if they do.
+    fn check(&self, values: &[f64]) -> Option<String> {
+        self.judge(self.stat.of(values))
+    }
+
+    /// Why `(value, weight)` pairs break the assertion, if they do.
+    fn check_weighted(&self, values: &[(f64, f64)]) -> Option<String> {
+        self.judge(self.stat.of_weighted(values))
+    }
+
+    /// Why the statistic being `value` breaks the assertion, if it does.
+    fn judge(&self, value: f64) -> Option<String> {
+        (!(self.test)(&value, &self.bound)).then(|| {
+            format!(
+                "assertion `{}` failed: {} is {}",
+                self.text,
+                stat_name(self.stat),
+                value
+            )
+        })
+    }
+}
+
+fn stat_name(stat: Stat) -> String {
+    match stat {
+        Stat::Count => "count".to_string(),
+        Stat::Sum => "sum".to_string(),
+        Stat::Mean => "mean".to_string(),
+        Stat::Stddev => "stddev".to_string(),
+        Stat::Min => "min".to_string(),
+        Stat::Max => "max".to_string(),
+        Stat::Percentile(p) => format!("p{}", p),
+    }
+}
+
+/// Unix seconds, or an RFC 3339 timestamp like `2024-05-01T12:00:00.5+02:00`, as Unix seconds.
+fn parse_time(s: &str) -> Option<f64> {
+    let s = s.trim();
+    if let Ok(seconds) = s.parse::<f64>() {
+        return Some(seconds);
+    }
+
+    let (date, time) = s.split_once(['T', 't', ' '])?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let (time, offset) = match time.strip_suffix(['Z', 'z']) {
+        Some(time) => (time, 0.0),
+        None => {
+            let (time, offset) = time.split_at(time.rfind(['+', '-'])?);
+            let (sign, offset) = offset.split_at(1);
+            let (hours, minutes) = offset.split_once(':')?;
+            let offset = hours.parse::<f64>().ok()? * 3600.0 + minutes.parse::<f64>().ok()? * 60.0;
+            (time, if sign == "-" { -offset } else { offset })
+        }
+    };
+    let mut time = time.splitn(3, ':');
+    let hours: f64 = time.next()?.parse().ok()?;
+    let minutes: f64 = time.next()?.parse().ok()?;
+    let seconds: f64 = time.next()?.parse().ok()?;
+
+    // Days since the epoch of the proleptic Gregorian date, counting years from March.
+    let (year, month) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    Some(days as f64 * 86400.0 + hours * 3600.0 + minutes * 60.0 + seconds - offset)
+}
+
+fn benford(out: &mut dyn Write, values: &[f64]) -> std::io::Result<()> {
+    let counts = analysis::first_digits(values);
+    let total = counts.iter().sum::<usize>() as f64;
+    if total == 0.0 {
+        fail(rhisto::Error::NoValues);
+    }
+
+    writeln!(out, "digit,count,fraction,expected")?;
+    for (i, (count, expected)) in counts.iter().zip(analysis::benford_expected()).enumerate() {
+        writeln!(
+            out,
+            "{},{},{:0.4},{:0.4}",
+            i + 1,
+            count,
+            *count as f64 / total,
+            expected
+        )?;
+    }
+
+    let (chi_square, p_value) = analysis::benford_chi_square(&counts);
+    writeln!(out)?;
+    writeln!(out, "chi_square,{:0.4}", chi_square)?;
+    writeln!(out, "p_value,{:0.4}", p_value)?;
+
+    Ok(())
+}
+
+/// Writes summary statistics of `a` and `b` side by side, followed by the tests asked for.
+///
+/// NaN values are left out of both inputs, as `--nan skip` leaves them out of histograms.
+fn compare(
+    out: &mut dyn Write,
+    a: &[f64],
+    b: &[f64],
+    ks: bool,
+    qq: Option<usize>,
+) -> std::io::Result<()> {
+    let a: Vec<f64> = a.iter().copied().filter(|v| !v.is_nan()).collect();
+    let b: Vec<f64> = b.iter().copied().filter(|v| !v.is_nan()).collect();
+    let (a, b) = (a.as_slice(), b.as_slice());
+
+    writeln!(out, "stat,a,b")?;
+    for ((name, a), (_, b)) in summary(a).into_iter().zip(summary(b)) {
+        writeln!(out, "{},{},{}", name, a, b)?;
+    }
+
+    if ks {
+        writeln!(out)?;
+        match analysis::ks_two_sample(a, b) {
+            Some((d, p_value)) => {
+                writeln!(out, "ks_d,{:0.4}", d)?;
+                writeln!(out, "ks_p_value,{:0.4}", p_value)?;
+            }
+            None => writeln!(out, "ks_d,")?,
+        }
+    }
+
+    if let Some(points) = qq {
+        writeln!(out)?;
+        writeln!(out, "q,a,b")?;
+        for (p, a, b) in analysis::qq_pairs(a, b, points) {
+            writeln!(out, "{},{},{}", p, a, b)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Bins each `--group-by` group of the input's rows on the same edges and writes how far
+/// each pair of groups diverge, then the groups by their mean divergence from the others.
+fn compare_groups(
+    out: &mut dyn Write,
+    input: &InputArgs,
+    column: u32,
+    num_bins: usize,
+) -> std::io::Result<()> {
+    let groups = read_groups(input, column);
+    let all: Vec<f64> = groups.values().flatten().copied().collect();
+    let edges = Histogram::from_values(all, num_bins)
+        .unwrap_or_else(|err| fail(err))
+        .into_edges();
+    let histos: Vec<Histogram> = groups
+        .values()
+        .map(|values| Histogram::with_edges(values, &edges).expect("binned edges ascend"))
+        .collect();
+    let divergences: Vec<Vec<f64>> = histos
+        .iter()
+        .map(|a| {
+            histos
+                .iter()
+                .map(|b| a.js_divergence(b).unwrap_or_else(|err| fail(err)))
+                .collect()
+        })
+        .collect();
+
+    let names: Vec<&str> = groups.keys().map(String::as_str).collect();
+    writeln!(out, "group,{}", names.join(","))?;
+    for (name, row) in names.iter().zip(&divergences) {
+        let row: Vec<String> = row.iter().map(|d| format!("{:0.4}", d)).collect();
+        writeln!(out, "{},{}", name, row.join(","))?;
+    }
+
+    let others = names.len().saturating_sub(1).max(1) as f64;
+    let mut ranked: Vec<(&str, f64)> = names
+        .iter()
+        .zip(&divergences)
+        .map(|(&name, row)| (name, row.iter().sum::<f64>() / others))
+        .collect();
+    ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+    writeln!(out)?;
+    writeln!(out, "group,mean_divergence")?;
+    for (name, mean) in ranked {
+        writeln!(out, "{},{:0.4}", name, mean)?;
+    }
+
+    Ok(())
+}
+
+fn sniff(out: &mut dyn Write, path: &std::path::Path) -> std::io::Result<()> {
+    let reader = BufReader::new(File::open(path).expect("failed to open input file"));
+    let sample: Vec<String> = reader
+        .lines()
+        .take(SNIFF_LINES)
+        .map(|line| line.expect("failed to read input file"))
+        .collect();
+    let Some(dialect) = rhisto::sniff::sniff(&sample.join("\n")) else {
+        fail(format!("{} has no lines to sniff", path.display()));
+    };
+
+    let columns: Vec<String> = dialect
+        .columns
+        .iter()
+        .map(|column| json_string(column.name()))
+        .collect();
+    writeln!(
+        out,
+        "{{\"delimiter\":{},\"quoted\":{},\"header\":{},\"columns\":[{}]}}",
+        json_string(&dialect.delimiter.to_string()),
+        dialect.quoted,
+        dialect.header,
+        columns.join(",")
+    )?;
+
+    Ok(())
+}
+
+/// Writes a line for each way each row breaks `schema`, returning how many there were.
+fn validate(
+    out: &mut dyn Write,
+    schema: &Schema,
+    rows: impl Iterator<Item = String>,
+    delim: &str,
+    skip_header: bool,
+) -> std::io::Result<usize> {
+    let first_line = if skip_header { 2 } else { 1 };
+    let mut violations = 0;
+    for (i, row) in rows.enumerate() {
+        for (column, violation) in schema.check(&row, delim) {
+            let problem = match violation {
+                Violation::Missing => "missing".to_string(),
+                Violation::WrongType(text) => {
+                    format!("`{}` does not parse as {}", text, column.kind.name())
+                }
+                Violation::OutOfRange(value) => format!(
+                    "{} is outside [{}, {}]",
+                    value,
+                    column.min.map_or("-inf".to_string(), |min| min.to_string()),
+                    column.max.map_or("inf".to_string(), |max| max.to_string())
+                ),
+            };
+            writeln!(
+                out,
+                "line {}: column {}: {}",
+                first_line + i,
+                column.describe(),
+                problem
+            )?;
+            violations += 1;
+        }
+    }
+
+    Ok(violations)
+}
+
+/// An awk command keeping the rows whose `column` falls in the `bin`th bin between `edges`.
+fn awk_filter(edges: &[f64], bin: usize, column: u32, delim: &str) -> String {
+    let (lower, upper) = match edges.get(bin..bin + 2) {
+        Some(&[lower, upper]) => (lower, upper),
+        _ => fail(format!(
+            "bin {} is out of range for a histogram of {} bins",
+            bin,
+            edges.len().saturating_sub(1)
+        )),
+    };
+
+    // awk fields are one indexed, and bins hold their upper edge but only the first its lower.
+    let field = format!("${}", column + 1);
+    let lower_op = if bin == 0 { ">=" } else { ">" };
+    // awk reads a separator longer than one character as a regex.
+    let separator = if delim.chars().count() > 1 {
+        regex::escape(delim)
+    } else {
+        delim.to_string()
+    };
+
+    format!(
+        "awk -F {} '{} {} {} && {} <= {}'",
+        shell_quote(&separator),
+        field,
+        lower_op,
+        lower,
+        field,
+        upper
+    )
+}
+
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn read_values(args: InputArgs) -> Vec<f64> {
+    read_values_and_rows(args, false).0
+}
+
+/// Reads the input's values, along with the row each value came from if `keep_rows` is set.
+fn read_values_and_rows(args: InputArgs, keep_rows: bool) -> (Vec<f64>, Vec<String>) {
+    if let Some(path) = &args.from_spill {
+        return (
+            read_spill(path).expect("failed to read spill file"),
+            Vec::new(),
+        );
+    }
+
+    let mut rows = Vec::new();
+    let values = parse_values(&args, keep_rows.then_some(&mut rows), |_| {});
+    if let Some(path) = &args.spill {
+        write_spill(path, &values).expect("failed to write spill file");
+    }
+
+    (values, rows)
+}
+
+/// Reads values like [`read_values_and_rows`] while they are estimated to fit in `limit`
+/// bytes, after which they are moved to a spill file as they are read.
+fn read_values_within(args: InputArgs, limit: u64, dir: &Path) -> Result<Vec<f64>, Spilled> {
+    let mut spilled: Option<Spilled> = None;
+    let values = parse_values(&args, None, |values| {
+        if spilled.is_some() || values.len() as u64 * BYTES_PER_VALUE > limit {
+            spilled
+                .get_or_insert_with(|| Spilled::create(dir).expect("failed to create spill file"))
+                .append(values.drain(..))
+                .expect("failed to write spill file");
+        }
+    });
+
+    match spilled {
+        Some(mut spilled) => {
+            spilled.append(values).expect("failed to write spill file");
+            Err(spilled)
+        }
+        None => Ok(values),
+    }
+}
+
+/// Parses the input's values, and its rows if `rows` is given, calling `each` with the values
+/// so far after each row or chunk of rows.
+fn parse_values(
+    args: &InputArgs,
+    mut rows: Option<&mut Vec<String>>,
+    mut each: impl FnMut(&mut Vec<f64>),
+) -> Vec<f64> {
+    let mut values = Vec::new();
+    match args.column {
+        // A bare column is parsed a chunk of rows at a time.
+        Some(column)
+            if rows.is_none()
+                && args.count_column.is_none()
+                && args.extract.is_none()
+                && args.map.is_none()
+                && args.cache_size.is_none() =>
+        {
+            let parser = ColumnParser::<f64>::single(column, &args.delim);
+            let mut errors = RowErrors::new(args);
+            let mut line = 0;
+            for chunk in read_chunks(
+                args.input.as_deref(),
+                args.skip_header,
+                args.record_sep,
+                args.progress,
+            ) {
+                errors.observe(&chunk);
+                let mut rest = &chunk[..];
+                while let Err((i, err)) = parser.parse_rows_batch(rest, &mut values) {
+                    errors.report(line + i, &rest[i], err.into());
+                    line += i + 1;
+                    rest = &rest[i + 1..];
+                }
+                line += rest.len();
+                each(&mut values);
+            }
+            errors.finish();
+        }
+        _ => for_each_row(args, extractor(args), |(value, count), row| {
+            values.extend(std::iter::repeat_n(value, count));
+            if let Some(rows) = rows.as_mut() {
+                rows.extend(std::iter::repeat_n(row, count));
+            }
+            each(&mut values);
+        }),
+    }
+
+    values
+}
+
+/// Calls `each` with what `extract` gets from each input row along with the row, writing rows
+/// it fails on to `--errors-out`.
+fn for_each_row<T>(
+    args: &InputArgs,
+    extract: impl Fn(&str) -> Result<T, RowError>,
+    mut each: impl FnMut(T, String),
+) {
+    let mut errors = RowErrors::new(args);
+    for (i, row) in read_rows(
+        args.input.as_deref(),
+        args.skip_header,
+        args.record_sep,
+        args.progress,
+    )
+    .enumerate()
+    {
+        errors.observe(std::slice::from_ref(&row));
+        match extract(&row) {
+            Ok(value) => each(value, row),
+            Err(err) => errors.report(i, &row, err),
+        }
+    }
+    errors.finish();
+}
+
+/// Where rows giving no value go: to `--errors-out` if given, otherwise malformed rows end the
+/// run and skipped ones are left out quietly.
+struct RowErrors {
+    writer: Option<BufWriter<File>>,
+    first_line: usize,
+    diagnostics: Diagnostics,
+}
+
+impl RowErrors {
+    fn new(args: &InputArgs) -> Self {
+        Self {
+            writer: args.errors_out.as_ref().map(|path| {
+                BufWriter::new(File::create(path).expect("failed to create errors file"))
+            }),
+            first_line: if args.skip_header { 2 } else { 1 },
+            diagnostics: Diagnostics::new(args),
+        }
+    }
+
+    /// Notes the next input rows for [`Diagnostics`], before any of them is reported.
+    fn observe<S: AsRef<str>>(&mut self, rows: &[S]) {
+        for row in rows {
+            if self.diagnostics.checked {
+                break;
+            }
+            self.diagnostics.observe(row.as_ref());
+        }
+    }
+
+    /// Reports the `i`th input row, counting from zero after any header.
+    fn report(&mut self, i: usize, row: &str, err: RowError) {
+        let line = self.first_line + i;
+        if i == 0
+            && !self.diagnostics.skip_header
+            && matches!(err, RowError::Failed("failed_parse", _))
+        {
+            warn(&Warning::UnskippedHeader);
+        }
+        match &mut self.writer {
+            Some(writer) => {
+                write_row_error(writer, line, row, &err).expect("failed to write errors file")
+            }
+            None => {
+                if let RowError::Failed(_, message) = err {
+                    self.diagnostics.check();
+                    fail(format!("line {}: {}", line, message));
+                }
+            }
+        }
+    }
+
+    fn finish(mut self) {
+        self.diagnostics.check();
+        if let Some(mut writer) = self.writer {
+            writer.flush().expect("failed to write errors file");
+        }
+    }
+}
+
+/// Looks over the first [`DIAGNOSTIC_ROWS`] input rows for likely mistakes in how they are
+/// read, warning about each on stderr once.
+struct Diagnostics {
+    delim: String,
+    column: Option<u32>,
+    skip_header: bool,
+    rows: Vec<String>,
+    checked: bool,
+}
+
+impl Diagnostics {
+    fn new(args: &InputArgs) -> Self {
+        Self {
+            delim: args.delim.clone(),
+            column: args.column,
+            skip_header: args.skip_header,
+            rows: Vec::new(),
+            checked: args.from_spill.is_some(),
+        }
+    }
+
+    fn observe(&mut self, row: &str) {
+        self.rows.push(row.to_string());
+        if self.rows.len() == DIAGNOSTIC_ROWS {
+            self.check();
+        }
+    }
+
+    /// Warns about what the rows noted so far suggest, unless that was already done.
+    fn check(&mut self) {
+        if !std::mem::replace(&mut self.checked, true) {
+            for warning in warnings(&self.rows, &self.delim, self.column) {
+                warn(&warning);
+            }
+            self.rows = Vec::new();
+        }
+    }
+}
+
+/// A likely mistake in how the input is read.
+#[derive(Debug, PartialEq)]
+enum Warning {
+    /// `--delim` is in none of the first rows, though the sniffed delimiter is.
+    DelimiterNotFound(String, char),
+    /// `--column` is empty in every one of the first rows, with the nearest column that is not.
+    EmptyColumn(u32, Option<usize>),
+    /// The first row failed to parse and `--skip-header` was not given.
+    UnskippedHeader,
+}
+
+impl Warning {
+    fn hint(&self) -> String {
+        match self {
+            Warning::DelimiterNotFound(_, sniffed) => {
+                format!("try `--delim {:?}`", sniffed.to_string())
+            }
+            Warning::EmptyColumn(_, Some(nearest)) => {
+                format!("columns count from zero; column {} has values", nearest)
+            }
+            Warning::EmptyColumn(_, None) => "columns count from zero".to_string(),
+            Warning::UnskippedHeader => "pass `--skip-header` if it is a header".to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Warning::DelimiterNotFound(delim, _) => {
+                write!(f, "the delimiter {:?} is in none of the first rows", delim)
+            }
+            Warning::EmptyColumn(column, _) => {
+                write!(f, "column {} is empty in all of the first rows", column)
+            }
+            Warning::UnskippedHeader => write!(f, "the first row failed to parse"),
+        }
+    }
+}
+
+fn warn(warning: &Warning) {
+    eprintln!("warning: {}\n  hint: {}", warning, warning.hint());
+}
+
+/// What the first rows of the input suggest is wrong with reading them split on `delim`,
+/// taking values from `column`.
+fn warnings(rows: &[String], delim: &str, column: Option<u32>) -> Vec<Warning> {
+    let rows: Vec<&str> = rows
+        .iter()
+        .map(String::as_str)
+        .filter(|row| !row.is_empty())
+        .collect();
+    let mut warnings = Vec::new();
+    if rows.iter().all(|row| !row.contains(delim))
+        && let Some(dialect) = rhisto::sniff::sniff(&rows.join("\n"))
+        && rows[0].contains(dialect.delimiter)
+    {
+        warnings.push(Warning::DelimiterNotFound(
+            delim.to_string(),
+            dialect.delimiter,
+        ));
+    }
+
+    if let Some(column) = column {
+        let fields: Vec<Vec<&str>> = rows.iter().map(|row| row.split(delim).collect()).collect();
+        let empty = |i: usize| {
+            fields
+                .iter()
+                .all(|fields| fields.get(i).is_none_or(|field| field.trim().is_empty()))
+        };
+        let width = fields.iter().map(Vec::len).max().unwrap_or(0);
+        if (column as usize) < width && empty(column as usize) {
+            let nearest = (0..width)
+                .filter(|&i| !empty(i))
+                .min_by_key(|&i| i.abs_diff(column as usize));
+            warnings.push(Warning::EmptyColumn(column, nearest));
+        }
+    }
+
+    warnings
+}
+
+/// A `--cache` file of `key,min,max,count` lines, one for each input it has seen.
+struct RangeCache {
+    path: PathBuf,
+    key: String,
+}
+
+impl RangeCache {
+    /// The entry for `input`, keyed by a hash of its path, size and modification time along
+    /// with the options that pick its values, so that changing any of them misses the cache.
+    fn new(path: &Path, input: &InputArgs) -> Self {
+        let file = input
+            .input
+            .as_deref()
+            .expect("clap requires input for cache");
+        let metadata = std::fs::metadata(file)
+            .unwrap_or_else(|err| fail(format!("failed to read `{}`: {}", file.display(), err)));
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |since| since.as_nanos());
+        let identity = format!(
+            "{}\n{}\n{}\n{:?}",
+            std::fs::canonicalize(file)
+                .unwrap_or_else(|_| file.to_path_buf())
+                .display(),
+            metadata.len(),
+            modified,
+            input
+        );
+        // FNV-1a, which unlike the std hashers is the same from one build to the next.
+        let hash = identity
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+            });
+
+        Self {
+            path: path.to_path_buf(),
+            key: format!("{:016x}", hash),
+        }
+    }
+
+    /// The cached range and count, unless there is no entry or the file cannot be read.
+    fn get(&self) -> Option<((f64, f64), usize)> {
+        let entries = std::fs::read_to_string(&self.path).ok()?;
+        entries.lines().find_map(|line| {
+            let mut fields = line.split(',');
+            if fields.next()? != self.key {
+                return None;
+            }
+            let min = fields.next()?.parse().ok()?;
+            let max = fields.next()?.parse().ok()?;
+            let count = fields.next()?.parse().ok()?;
+            Some(((min, max), count))
+        })
+    }
+
+    /// Replaces the entry with `range` and `count`, or drops it if there is no range.
+    fn store(&self, range: Option<(f64, f64)>, count: usize) {
+        let entries = std::fs::read_to_string(&self.path).unwrap_or_default();
+        let mut lines: Vec<String> = entries
+            .lines()
+            .filter(|line| line.split(',').next() != Some(&self.key))
+            .map(str::to_string)
+            .collect();
+        if let Some((min, max)) = range {
+            lines.push(format!("{},{:?},{:?},{}", self.key, min, max, count));
+        }
+        let contents: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+        std::fs::write(&self.path, contents).unwrap_or_else(|err| {
+            fail(format!(
+                "failed to write `{}`: {}",
+                self.path.display(),
+                err
+            ))
+        });
+    }
+}
+
+/// Values `--memory-limit` moved out of memory, in a temporary file removed when dropped.
+struct Spilled {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    count: usize,
+    /// The lowest and highest finite values written.
+    range: Option<(f64, f64)>,
+}
+
+impl Spilled {
+    fn create(dir: &Path) -> std::io::Result<Self> {
+        let path = dir.join(format!("rhisto-{}.spill", std::process::id()));
+        Ok(Self {
+            writer: BufWriter::new(File::create(&path)?),
+            path,
+            count: 0,
+            range: None,
+        })
+    }
+
+    fn append(&mut self, values: impl IntoIterator<Item = f64>) -> std::io::Result<()> {
+        for value in values {
+            self.writer.write_all(&value.to_le_bytes())?;
+            self.count += 1;
+            if value.is_finite() {
+                self.range = Some(match self.range {
+                    Some((min, max)) => (min.min(value), max.max(value)),
+                    None => (value, value),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the values back in the order they were written.
+    fn values(&mut self) -> std::io::Result<impl Iterator<Item = f64>> {
+        self.writer.flush()?;
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        Ok(std::iter::from_fn(move || {
+            let mut bytes = [0; 8];
+            reader
+                .read_exact(&mut bytes)
+                .ok()
+                .map(|()| f64::from_le_bytes(bytes))
+        }))
+    }
+}
+
+impl Drop for Spilled {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Where `--memory-limit` and `--exact-quantiles` keep their temporary files.
+fn tmpdir(args: &HistogramArgs) -> PathBuf {
+    args.tmpdir.clone().unwrap_or_else(std::env::temp_dir)
+}
+
+/// Sorts more values than fit in memory for `--exact-quantiles`, as sorted runs of at most
+/// `run_len` values in temporary files, which are merged to find the quantiles and removed
+/// when dropped.
+struct ExternalSort {
+    dir: PathBuf,
+    run_len: usize,
+    buffer: Vec<f64>,
+    runs: Vec<PathBuf>,
+    count: usize,
+}
+
+/// A value read from a run, ordered by [`f64::total_cmp`] and then by its run.
+#[derive(PartialEq)]
+struct Head(f64, usize);
+
+impl Eq for Head {}
+
+impl PartialOrd for Head {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Head {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+impl ExternalSort {
+    fn new(dir: &Path, run_len: usize) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            run_len,
+            buffer: Vec::new(),
+            runs: Vec::new(),
+            count: 0,
+        }
+    }
+
+    /// Adds `value`, unless it is NaN, writing out a run once the buffer is full.
+    fn push(&mut self, value: f64) -> std::io::Result<()> {
+        if value.is_nan() {
+            return Ok(());
+        }
+        self.buffer.push(value);
+        self.count += 1;
+        if self.buffer.len() >= self.run_len {
+            self.write_run()?;
+        }
+        Ok(())
+    }
+
+    fn write_run(&mut self) -> std::io::Result<()> {
+        self.buffer.sort_by(f64::total_cmp);
+        let path = self.dir.join(format!(
+            "rhisto-{}-{}.run",
+            std::process::id(),
+            self.runs.len()
+        ));
+        // Pushed before writing, so that a failed write is still cleaned up.
+        self.runs.push(path.clone());
+        write_spill(&path, &self.buffer)?;
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Each of `quantiles`, from 0 to 1, interpolated between the sorted values as
+    /// [`analysis::quantile`] does, or NaN if there are no values.
+    fn quantiles(mut self, quantiles: &[f64]) -> std::io::Result<Vec<f64>> {
+        if self.count == 0 {
+            return Ok(vec![f64::NAN; quantiles.len()]);
+        }
+        let ranks: Vec<f64> = quantiles
+            .iter()
+            .map(|q| q * (self.count - 1) as f64)
+            .collect();
+        let mut wanted: BTreeMap<usize, f64> = ranks
+            .iter()
+            .flat_map(|rank| [rank.floor() as usize, rank.ceil() as usize])
+            .map(|i| (i, f64::NAN))
+            .collect();
+
+        if self.runs.is_empty() {
+            self.buffer.sort_by(f64::total_cmp);
+            for (&i, value) in &mut wanted {
+                *value = self.buffer[i];
+            }
+        } else {
+            if !self.buffer.is_empty() {
+                self.write_run()?;
+            }
+            let mut readers = self
+                .runs
+                .iter()
+                .map(|path| File::open(path).map(BufReader::new))
+                .collect::<std::io::Result<Vec<_>>>()?;
+            let next = |reader: &mut BufReader<File>| {
+                let mut bytes = [0; 8];
+                reader
+                    .read_exact(&mut bytes)
+                    .ok()
+                    .map(|()| f64::from_le_bytes(bytes))
+            };
+            let mut heads: std::collections::BinaryHeap<std::cmp::Reverse<Head>> = readers
+                .iter_mut()
+                .enumerate()
+                .filter_map(|(run, reader)| Some(std::cmp::Reverse(Head(next(reader)?, run))))
+                .collect();
+            let last = *wanted.keys().next_back().expect("there is a quantile");
+            for i in 0..=last {
+                let std::cmp::Reverse(Head(value, run)) =
+                    heads.pop().expect("the runs hold every value");
+                if let Some(wanted) = wanted.get_mut(&i) {
+                    *wanted = value;
+                }
+                if let Some(value) = next(&mut readers[run]) {
+                    heads.push(std::cmp::Reverse(Head(value, run)));
+                }
+            }
+        }
+
+        Ok(ranks
+            .iter()
+            .map(|rank| {
+                let (lower, upper) = (
+                    wanted[&(rank.floor() as usize)],
+                    wanted[&(rank.ceil() as usize)],
+                );
+                lower + (upper - lower) * (rank - rank.floor())
+            })
+            .collect())
+    }
+}
+
+impl Drop for ExternalSort {
+    fn drop(&mut self) {
+        for run in &self.runs {
+            let _ = std::fs::remove_file(run);
+        }
+    }
+}
+
+/// Writes `values` as consecutive little-endian `f64`s.
+fn write_spill(path: &std::path::Path, values: &[f64]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for value in values {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+
+    writer.flush()
+}
+
+fn read_spill(path: &std::path::Path) -> std::io::Result<Vec<f64>> {
+    let bytes = std::fs::read(path)?;
+    Ok(bytes
+        .chunks_exact(8)
+        .map(|chunk| f64::from_le_bytes(chunk.try_into().expect("chunks are eight bytes")))
+        .collect())
+}
+
+fn read_rows(
+    input: Option<&Path>,
+    skip_header: bool,
+    record_sep: u8,
+    progress: bool,
+) -> impl Iterator<Item = String> {
+    read_chunks(input, skip_header, record_sep, progress).flatten()
+}
+
+/// Reads the input's rows, separated by `record_sep`, on a thread of their own, handing them
+/// over a chunk at a time so reading the next chunk overlaps parsing the last one.
+///
+/// With `progress`, a `--progress` ticker runs until the last chunk is taken.
+fn read_chunks(
+    input: Option<&Path>,
+    skip_header: bool,
+    record_sep: u8,
+    progress: bool,
+) -> impl Iterator<Item = Vec<String>> {
+    let mut reader: Box<dyn BufRead + Send> = match input {
+        Some(path_buf) => Box::new(BufReader::new(
+            File::open(path_buf).expect("failed to open input file"),
+        )),
+        None => Box::new(BufReader::new(std::io::stdin())),
+    };
+
+    if skip_header {
+        reader
+            .skip_until(record_sep)
+            .expect("failed to skip header");
+    }
+
+    // One chunk waits in the channel while the next is read, so two are in flight at most.
+    let (chunks, received) = std::sync::mpsc::sync_channel(1);
+    let rows_read = Arc::new(AtomicUsize::new(0));
+    let (done, ticking) = std::sync::mpsc::channel::<()>();
+    let mut ticker = progress.then(|| {
+        let rows_read = rows_read.clone();
+        std::thread::spawn(move || tick(&rows_read, ticking))
+    });
+    std::thread::spawn(move || {
+        let _done = done;
+        let mut lines = reader.split(record_sep).map(move |record| {
+            let mut record = record?;
+            // Like `BufRead::lines`, newline separated records may end in a carriage return.
+            if record_sep == b'\n' && record.last() == Some(&b'\r') {
+                record.pop();
+            }
+            String::from_utf8(record)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+        });
+        loop {
+            // Stop at the next row after Ctrl-C so whatever was read so far is still binned.
+            let chunk: std::io::Result<Vec<String>> = lines
+                .by_ref()
+                .take_while(|_| !INTERRUPTED.load(Ordering::Relaxed))
+                .inspect(|_| {
+                    if progress {
+                        rows_read.fetch_add(1, Ordering::Relaxed);
+                    }
+                })
+                .take(CHUNK_ROWS)
+                .collect();
+            let last = chunk
+                .as_ref()
+                .map_or(true, |chunk| chunk.len() < CHUNK_ROWS);
+            if chunks.send(chunk).is_err() || last {
+                break;
+            }
+        }
+    });
+
+    received
+        .into_iter()
+        .map(|chunk| chunk.expect("failed to read input"))
+        .chain(std::iter::from_fn(move || {
+            // Let the ticker write its last line before the output.
+            if let Some(ticker) = ticker.take() {
+                let _ = ticker.join();
+            }
+            None
+        }))
+}
+
+/// Writes the rate and total of rows read to stderr every [`PROGRESS_INTERVAL`] until
+/// `done` disconnects, then the overall rate. A terminal gets one line rewritten in place.
+fn tick(rows_read: &AtomicUsize, done: std::sync::mpsc::Receiver<()>) {
+    let terminal = std::io::stderr().is_terminal();
+    let report = |line: String| match terminal {
+        true => eprint!("\r{}\x1b[K", line),
+        false => eprintln!("{}", line),
+    };
+
+    let start = std::time::Instant::now();
+    let mut last = 0;
+    while let Err(RecvTimeoutError::Timeout) = done.recv_timeout(PROGRESS_INTERVAL) {
+        let rows = rows_read.load(Ordering::Relaxed);
+        let rate = (rows - last) as f64 / PROGRESS_INTERVAL.as_secs_f64();
+        report(format!("{:.0} rows/s, {} rows", rate, rows));
+        last = rows;
+    }
+
+    let rows = rows_read.load(Ordering::Relaxed);
+    let elapsed = start.elapsed().as_secs_f64();
+    report(format!(
+        "{:.0} rows/s, {} rows in {:.1}s",
+        rows as f64 / elapsed,
+        rows,
+        elapsed
+    ));
+    if terminal {
+        eprintln!();
+    }
+}
+
+/// Why an input row gave no value.
+#[derive(Debug, PartialEq)]
+enum RowError {
+    /// Left out on purpose, e.g. by `--extract` not matching or `--zero-policy skip`.
+    Skipped(&'static str),
+    /// Malformed, which ends the run unless `--errors-out` is given.
+    Failed(&'static str, String),
+}
+
+impl From<rhisto::Error> for RowError {
+    fn from(err: rhisto::Error) -> Self {
+        match err {
+            rhisto::Error::MissingColumn(_, column) => {
+                RowError::Failed("missing_column", format!("no column {}", column))
+            }
+            rhisto::Error::FailedParse(value, ty) => {
+                RowError::Failed("failed_parse", format!("`{}` is not a {}", value, ty))
+            }
+            err => RowError::Failed("error", format!("{:?}", err)),
+        }
+    }
+}
+
+/// Writes a `--errors-out` JSON line for the row on `line`.
+fn write_row_error(
+    writer: &mut dyn Write,
+    line: usize,
+    row: &str,
+    err: &RowError,
+) -> std::io::Result<()> {
+    let (kind, message) = match err {
+        RowError::Skipped(kind) => (kind, None),
+        RowError::Failed(kind, message) => (kind, Some(message)),
+    };
+    write!(
+        writer,
+        "{{\"line\":{},\"row\":{},\"kind\":{}",
+        line,
+        json_string(row),
+        json_string(kind)
+    )?;
+    if let Some(message) = message {
+        write!(writer, ",\"message\":{}", json_string(message))?;
+    }
+    writeln!(writer, "}}")
+}
+
+/// A `--weight-column` field, which has to be a finite, non-negative number.
+fn parse_weight(s: &str) -> Result<f64, RowError> {
+    match s.trim().parse::<f64>() {
+        Ok(weight) if weight.is_finite() && weight >= 0.0 => Ok(weight),
+        _ => Err(RowError::Failed(
+            "bad_weight",
+            format!("`{}` is not a non-negative weight", s),
+        )),
+    }
+}
+
+/// A `--count-column` field, which has to be a whole number of occurrences.
+fn parse_count(s: &str) -> Result<usize, RowError> {
+    s.trim().parse().map_err(|_| {
+        RowError::Failed(
+            "bad_count",
+            format!("`{}` is not a whole, non-negative count", s),
+        )
+    })
+}
+
+fn parse_value(s: &str) -> Result<f64, RowError> {
+    s.parse()
+        .map_err(|_| RowError::Failed("failed_parse", format!("`{}` is not a number", s)))
+}
+
+/// Parses `--expr` once, so rows only bind its variables.
+fn compile(expr: &str) -> Expr {
+    expr.parse()
+        .unwrap_or_else(|err| fail(format!("invalid expression `{}`: {}", expr, err)))
+}
+
+/// Evaluates `expr` with `vars` bound on top of meval's builtin constants and functions.
+fn eval(expr: &Expr, vars: Vec<(&str, f64)>, builtins: &Context) -> Result<f64, RowError> {
+    expr.eval_with_context((vars, builtins))
+        .map_err(|err| RowError::Failed("bad_expression", err.to_string()))
+}
+
+/// Turns an input row into its value and how many times it occurs.
+type Extractor = Box<dyn Fn(&str) -> Result<(f64, usize), RowError>>;
+
+fn extractor(args: &InputArgs) -> Extractor {
+    if let (Some(column), Some(count_column)) = (args.column, args.count_column) {
+        let value = column_value(column, args);
+        let counts = ColumnParser::<String>::single(count_column, &args.delim);
+        Box::new(move |row| Ok((value(row)?, parse_count(&counts.parse_row(row)?[0])?)))
+    } else if let Some(column) = args.column {
+        let value = column_value(column, args);
+        Box::new(move |row| Ok((value(row)?, 1)))
+    } else if let Some((a, b)) = args.diff {
+        let parser = ColumnParser::<f64>::new(&[a, b], &args.delim);
+        Box::new(move |row| {
+            let vals = parser.parse_row(row)?;
+            Ok((vals[0] - vals[1], 1))
+        })
+    } else if let Some((a, b)) = args.ratio {
+        let parser = ColumnParser::<f64>::new(&[a, b], &args.delim);
+        let (zero_policy, zero_epsilon) = (args.zero_policy, args.zero_epsilon);
+        Box::new(move |row| {
+            let vals = parser.parse_row(row)?;
+            let (num, den) = (vals[0], vals[1]);
+            if den.abs() > zero_epsilon {
+                return Ok((num / den, 1));
+            }
+
+            match zero_policy {
+                ZeroPolicy::Skip => Err(RowError::Skipped("zero_denominator")),
+                ZeroPolicy::Clamp => Ok((num / zero_epsilon.copysign(den), 1)),
+                ZeroPolicy::Infinity if num == 0.0 => Err(RowError::Skipped("zero_over_zero")),
+                ZeroPolicy::Infinity => Ok((f64::INFINITY.copysign(num * den.signum()), 1)),
+            }
+        })
+    } else if args.preset == Some(Preset::JournalJson) {
+        if args.expr.is_some() {
+            fail("`--preset journal-json` bins a `--field`, not an `--expr`");
+        }
+        let field = args.field.clone().unwrap_or_else(|| "PRIORITY".to_string());
+        Box::new(move |row| {
+            let value = journal_field(row, &field).ok_or(RowError::Skipped("missing_field"))?;
+            Ok((parse_value(value)?, 1))
+        })
+    } else if let Some(pattern) = args
+        .pattern
+        .clone()
+        .or_else(|| args.preset.and_then(Preset::regex))
+    {
+        let names: Vec<String> = pattern
+            .capture_names()
+            .flatten()
+            .map(str::to_string)
+            .collect();
+        let field = args.field.clone().unwrap_or_else(|| names[0].clone());
+        if !names.contains(&field) {
+            fail(format!(
+                "no `{}` field, expected one of {}",
+                field,
+                names.join(", ")
+            ));
+        }
+        let expr = args.expr.as_deref().map(compile);
+        let builtins = Context::new();
+        Box::new(move |row| {
+            let captures = pattern.captures(row).ok_or(RowError::Skipped("no_match"))?;
+            let capture = |name: &str| captures.name(name).map(|m| parse_value(m.as_str()));
+
+            let Some(expr) = &expr else {
+                let value = capture(&field).ok_or(RowError::Skipped("missing_field"))??;
+                return Ok((value, 1));
+            };
+
+            let mut vars = Vec::with_capacity(names.len());
+            for name in &names {
+                if let Some(value) = capture(name) {
+                    vars.push((name.as_str(), value?));
+                }
+            }
+
+            Ok((eval(expr, vars, &builtins)?, 1))
+        })
+    } else {
+        let expr = args
+            .expr
+            .clone()
+            .expect("clap arg groups enforce expr must be Some");
+
+        let column_expr = ColumnExpr::new(&expr, &args.delim);
+        let columns = column_expr.columns.clone();
+        let value: ColumnValue = match FastExpr::parse(&expr) {
+            Some(fast) => {
+                let parser = column_expr.parser;
+                Box::new(move |row| Ok(fast.eval(&parser.parse_row(row)?)))
+            }
+            None => {
+                let builtins = Context::new();
+                Box::new(move |row| column_expr.eval(row, &builtins))
+            }
+        };
+        let value = cached(&columns, args, value);
+        Box::new(move |row| Ok((value(row)?, 1)))
+    }
+}
+
+/// An expression over `?`ed column indices, like `--expr`.
+struct ColumnExpr {
+    columns: Vec<u32>,
+    parser: ColumnParser<f64>,
+    vars: Vec<String>,
+    compiled: Expr,
+}
+
+impl ColumnExpr {
+    fn new(expr: &str, delim: &str) -> Self {
+        let re = Regex::new(r"\?([0-9]*)").unwrap();
+        let columns: Vec<u32> = re
+            .captures_iter(expr)
+            .map(|c| c.extract())
+            .map(|(_, [col])| col.parse::<u32>().unwrap())
+            .collect();
+
+        Self {
+            parser: ColumnParser::<f64>::new(&columns[..], delim),
+            vars: columns.iter().map(|col| format!("_{}", col)).collect(),
+            compiled: compile(&expr.replace("?", "_")),
+            columns,
+        }
+    }
+
+    fn eval(&self, row: &str, builtins: &Context) -> Result<f64, RowError> {
+        let vals = self.parser.parse_row(row)?;
+        let vars = self.vars.iter().map(String::as_str).zip(vals).collect();
+        eval(&self.compiled, vars, builtins)
+    }
+}
+
+/// A `--where` condition: comparisons between column expressions, joined by `&&`, e.g.
+/// `?2 > 0 && ?1 / ?2 <= 10`.
+struct Condition(Vec<(ColumnExpr, Comparison, ColumnExpr)>);
+
+type Comparison = fn(&f64, &f64) -> bool;
+
+/// Splits `comparison` around its first comparison operator.
+fn split_comparison(comparison: &str) -> Option<(&str, Comparison, &str)> {
+    let (i, op, test) = [
+        (">=", f64::ge as Comparison),
+        ("<=", f64::le),
+        ("==", f64::eq),
+        ("!=", f64::ne),
+        (">", f64::gt),
+        ("<", f64::lt),
+    ]
+    .into_iter()
+    .find_map(|(op, test)| Some((comparison.find(op)?, op, test)))?;
+
+    Some((&comparison[..i], test, &comparison[i + op.len()..]))
+}
+
+impl Condition {
+    fn new(condition: &str, delim: &str) -> Self {
+        let comparisons = condition
+            .split("&&")
+            .map(|comparison| {
+                let (lhs, test, rhs) = split_comparison(comparison).unwrap_or_else(|| {
+                    fail(format!("`{}` is not a comparison", comparison.trim()))
+                });
+                (
+                    ColumnExpr::new(lhs, delim),
+                    test,
+                    ColumnExpr::new(rhs, delim),
+                )
+            })
+            .collect();
+
+        Self(comparisons)
+    }
+
+    fn holds(&self, row: &str, builtins: &Context) -> Result<bool, RowError> {
+        for (lhs, test, rhs) in &self.0 {
+            if !test(&lhs.eval(row, builtins)?, &rhs.eval(row, builtins)?) {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// An `--expr` simple enough to evaluate without building a meval context for every row: a
+/// column or constant, optionally combined with another by one arithmetic operator.
+#[derive(Debug, PartialEq)]
+struct FastExpr {
+    lhs: Operand,
+    rhs: Option<(char, Operand)>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Operand {
+    /// The position of the column among the expression's `?` columns, in order.
+    Column(usize),
+    Constant(f64),
+}
+
+impl FastExpr {
+    fn parse(expr: &str) -> Option<Self> {
+        let operand = r"(\?[0-9]+|[0-9]*\.?[0-9]+(?:[eE][-+]?[0-9]+)?)";
+        let re = Regex::new(&format!(r"^\s*{0}\s*(?:([-+*/])\s*{0}\s*)?$", operand)).unwrap();
+        let captures = re.captures(expr)?;
+
+        let mut columns = 0;
+        let mut operand = |text: &str| match text.strip_prefix('?') {
+            Some(_) => {
+                columns += 1;
+                Some(Operand::Column(columns - 1))
+            }
+            None => text.parse().ok().map(Operand::Constant),
+        };
+        let lhs = operand(&captures[1])?;
+        let rhs = match (captures.get(2), captures.get(3)) {
+            (Some(op), Some(text)) => Some((op.as_str().chars().next()?, operand(text.as_str())?)),
+            _ => None,
+        };
+
+        Some(Self { lhs, rhs })
+    }
+
+    /// Evaluates the expression with `columns` holding its `?` columns' values in order.
+    fn eval(&self, columns: &[f64]) -> f64 {
+        let value = |operand: &Operand| match *operand {
+            Operand::Column(i) => columns[i],
+            Operand::Constant(value) => value,
+        };
+        let lhs = value(&self.lhs);
+        match &self.rhs {
+            None => lhs,
+            Some(('+', rhs)) => lhs + value(rhs),
+            Some(('-', rhs)) => lhs - value(rhs),
+            Some(('*', rhs)) => lhs * value(rhs),
+            Some((_, rhs)) => lhs / value(rhs),
+        }
+    }
+}
+
+type ColumnText = Box<dyn Fn(&str) -> Result<String, RowError>>;
+
+/// Reads the text of `column`, or of its `--extract` capture.
+fn column_text(column: u32, args: &InputArgs) -> ColumnText {
+    let parser = ColumnParser::<String>::single(column, &args.delim);
+    let extract = args.extract.clone();
+    Box::new(move |row| {
+        let field = parser.parse_row(row)?.remove(0);
+        match &extract {
+            Some(re) => re
+                .captures(&field)
+                .map(|captures| captures[1].to_string())
+                .ok_or(RowError::Skipped("no_match")),
+            None => Ok(field),
+        }
+    })
+}
+
+type ColumnValue = Box<dyn Fn(&str) -> Result<f64, RowError>>;
+
+/// Reads the value of `column`, after any `--extract` and `--map` lookup.
+fn column_value(column: u32, args: &InputArgs) -> ColumnValue {
+    if args.map.is_none() && args.extract.is_none() {
+        let parser = ColumnParser::<f64>::single(column, &args.delim);
+        return cached(
+            &[column],
+            args,
+            Box::new(move |row| Ok(parser.parse_row(row)?[0])),
+        );
+    }
+
+    let field = column_text(column, args);
+
+    let Some(path) = &args.map else {
+        return cached(
+            &[column],
+            args,
+            Box::new(move |row| parse_value(&field(row)?)),
+        );
+    };
+
+    let keys = ColumnParser::<String>::single(args.map_key, &args.delim);
+    let values = ColumnParser::<f64>::single(args.map_value, &args.delim);
+    let mapping: HashMap<String, f64> =
+        BufReader::new(File::open(path).expect("failed to open map file"))
+            .lines()
+            .map(|row| row.expect("failed to read map file"))
+            .enumerate()
+            .map(|(i, row)| {
+                keys.parse_row(&row)
+                    .and_then(|mut key| Ok((key.remove(0), values.parse_row(&row)?[0])))
+                    .unwrap_or_else(|err| {
+                        fail(format!("{} line {}: {}", path.display(), i + 1, err))
+                    })
+            })
+            .collect();
+
+    Box::new(move |row| {
+        let key = field(row)?;
+        mapping.get(&key).copied().ok_or_else(|| {
+            RowError::Failed(
+                "unmapped",
+                format!("no value for `{}` in the map file", key),
+            )
+        })
+    })
+}
+
+/// Remembers what `value` gives for the text of `columns` in up to `--cache-size` recently
+/// seen rows, so rows repeating it skip parsing and evaluating. Without `--cache-size` this is
+/// `value` itself.
+fn cached(columns: &[u32], args: &InputArgs, value: ColumnValue) -> ColumnValue {
+    let Some(capacity) = args.cache_size else {
+        return value;
+    };
+
+    let parser = ColumnParser::<String>::new(columns, &args.delim);
+    let delim = args.delim.clone();
+    let cache = RefCell::new(LruCache::new(capacity));
+    Box::new(move |row| {
+        let mut fields = parser.parse_row(row)?;
+        let key = if fields.len() == 1 {
+            fields.remove(0)
+        } else {
+            fields.join(&delim)
+        };
+        if let Some(&value) = cache.borrow_mut().get(&key) {
+            return Ok(value);
+        }
+
+        let value = value(row)?;
+        cache.borrow_mut().insert(key, value);
+        Ok(value)
+    })
+}
+
+/// Parses a `--pattern`, which needs at least one named capture group.
+fn parse_pattern(s: &str) -> Result<Regex, String> {
+    let re = Regex::new(s).map_err(|err| err.to_string())?;
+    if re.capture_names().flatten().next().is_none() {
+        return Err("expected a named capture group, e.g. `(?P<ms>\\d+)`".to_string());
+    }
+
+    Ok(re)
+}
+
+/// Parses an `--extract` pattern, which needs exactly one capture group for the value.
+fn parse_extract(s: &str) -> Result<Regex, String> {
+    let re = Regex::new(s).map_err(|err| err.to_string())?;
+    if re.captures_len() != 2 {
+        return Err(format!(
+            "expected one capture group but found {}",
+            re.captures_len() - 1
+        ));
+    }
+
+    Ok(re)
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about, long_about = None, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    histogram: HistogramArgs,
+
+    /// Page output through `$PAGER`, or `less -R`, when writing to a terminal.
+    #[arg(long, global = true, default_value_t = false)]
+    pager: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Report the percentile rank of a value within the input's distribution.
+    Rank {
+        /// The value to rank.
+        #[arg(long, allow_negative_numbers = true)]
+        value: f64,
+
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    /// Detect values heaped on round numbers (multiples of 5, 10 and 100).
+    Heaping {
+        /// How many times the count of its neighbors a round value needs to be reported.
+        #[arg(long, default_value_t = 3.0)]
+        min_ratio: f64,
+
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    /// Compare the distributions of the values in two inputs, or of groups of one input's rows.
+    ///
+    /// The column, expression and parsing options apply to both inputs.
+    #[command(mut_arg("input", |arg| {
+        arg.required(true)
+            .index(1)
+            .help("The first buffer to read data from")
+    }))]
+    Compare {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// The buffer to compare the first input against.
+        #[arg(index = 2, required_unless_present = "group_by")]
+        other: Option<PathBuf>,
+
+        /// Compare the groups of rows sharing a value in this zero indexed column instead of
+        /// two inputs, e.g. to find the one bad host in a fleet.
+        ///
+        /// Writes the Jensen–Shannon divergence between each pair of groups' histograms, then
+        /// each group's mean divergence from the others, most divergent first.
+        #[arg(long, value_name = "COLUMN", conflicts_with_all = ["other", "ks", "qq"])]
+        group_by: Option<u32>,
+
+        /// The number of bins the `--group-by` groups share.
+        #[arg(short, long, default_value_t = 10, requires = "group_by")]
+        num_bins: usize,
+
+        /// Run a two-sample Kolmogorov–Smirnov test on the raw values.
+        #[arg(long, default_value_t = false)]
+        ks: bool,
+
+        /// Write this many pairs of matching quantiles of the two inputs, for a Q–Q plot.
+        #[arg(
+            long,
+            value_name = "POINTS",
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "100",
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+        )]
+        qq: Option<usize>,
+    },
+
+    /// Print an awk command that keeps the input rows falling in one bin of a saved histogram.
+    ///
+    /// For drilling down into a bin, e.g. `eval "$(rhisto emit-filter latency.rhst 7 -c 2) input.csv"`.
+    EmitFilter {
+        /// A histogram saved with `--format-out rhst`.
+        histogram: PathBuf,
+
+        /// The zero indexed bin whose rows to keep.
+        #[arg(value_name = "BIN_INDEX")]
+        bin: usize,
+
+        /// The zero indexed column the histogram was computed from.
+        #[arg(short, long)]
+        column: u32,
+
+        /// The delimeting pattern used to separate columns in the input.
+        #[arg(short, long, default_value = ",")]
+        delim: String,
+    },
+
+    /// Add up saved histograms with the same bins, printing the total as `.rhst`.
+    Merge {
+        /// Histograms saved with `--format-out rhst`.
+        #[arg(required = true)]
+        histograms: Vec<PathBuf>,
+    },
+
+    /// Subtract one saved histogram's counts from another's with the same bins, printing the
+    /// signed difference as `.rhst`.
+    Diff {
+        /// The histogram saved with `--format-out rhst` to subtract from.
+        a: PathBuf,
+
+        /// The histogram with the same bins to subtract.
+        b: PathBuf,
+    },
+
+    /// Draw values from a saved histogram, one a line, as synthetic input for simulations.
+    ///
+    /// Bins are picked in proportion to their counts and values spread uniformly within them.
+    Sample {
+        /// A histogram saved with `--format-out rhst`.
+        histogram: PathBuf,
+
+        /// How many values to draw.
+        #[arg(long, default_value_t = 1000)]
+        rows: usize,
+
+        /// Seeds the draws, so the same seed draws the same values.
+        #[arg(long, default_value_t = 1)]
+        seed: u64,
+    },
+
+    /// Guess the delimiter, quoting, header and column types of an input, printed as JSON.
+    Sniff {
+        /// The buffer to sniff.
+        input: PathBuf,
+    },
+
+    /// Check each input row against the column types and ranges declared in a schema,
+    /// reporting every violation and exiting non-zero if there are any.
+    Validate {
+        /// A TOML file with a `[[column]]` table per column, each with an `index` and optional
+        /// `name`, `type` (`integer`, `float` or `string`), `min` and `max`.
+        #[arg(long)]
+        schema: PathBuf,
+
+        /// The optional buffer to read data from.
+        input: Option<PathBuf>,
+
+        /// The delimeting pattern used to separate columns in the input.
+        #[arg(short, long, default_value = ",")]
+        delim: String,
+
+        /// Indicate whether the input data contains a header row.
+        #[arg(short, long, default_value_t = false)]
+        skip_header: bool,
+    },
+
+    /// Tally first significant digits and test them against Benford's law.
+    Benford {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+
+    /// Write one statistic of the values in each time window, instead of a histogram.
+    ///
+    /// Rows are `window_start,stat`, windows aligned to the Unix epoch and in time order, with
+    /// empty windows left out.
+    Trend {
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// The zero indexed column holding each row's time, as Unix seconds or an RFC 3339
+        /// timestamp like `2024-05-01T12:00:00Z`. Optional with `--preset journal-json`.
+        #[arg(long, value_name = "COLUMN", required_unless_present = "preset")]
+        time_column: Option<u32>,
+
+        /// The length of each window, in seconds or with an `s`, `m`, `h`, `d` or `w`
+        /// suffix, e.g. `1h`.
+        #[arg(long, value_parser = parse_duration)]
+        window: f64,
+
+        /// The statistic: `count`, `sum`, `mean`, `stddev`, `min`, `max`, `median` or a
+        /// percentile like `p99` or `p99.9`.
+        #[arg(long, default_value = "mean", value_parser = parse_stat)]
+        stat: Stat,
+    },
+
+    /// Print the values of input rows without binning them, like a small awk.
+    ///
+    /// Each kept row prints its `--column` fields as they are, then its `--expr` values, joined
+    /// by `--delim`.
+    Extract {
+        /// The optional buffer to read data from.
+        input: Option<PathBuf>,
+
+        /// Comma separated zero indexed columns to print, e.g. `2,5`.
+        #[arg(short, long, value_delimiter = ',', required_unless_present = "expr")]
+        column: Vec<u32>,
+
+        /// An expression over `?`ed column indices to print, like histogram `--expr`. May be
+        /// given more than once.
+        #[arg(short, long)]
+        expr: Vec<String>,
+
+        /// Only print rows meeting this condition: comparisons of expressions over `?`ed
+        /// columns using `<`, `<=`, `==`, `!=`, `>=` or `>`, joined by `&&`, e.g. `'?2>0'`.
+        #[arg(long = "where", value_name = "CONDITION")]
+        condition: Option<String>,
+
+        /// The delimeting pattern used to separate columns in the input and output.
+        #[arg(short, long, default_value = ",")]
+        delim: String,
+
+        /// Indicate whether the input data contains a header row.
+        #[arg(short, long, default_value_t = false)]
+        skip_header: bool,
+    },
+}
+
+/// A statistic of the values in a `trend` window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stat {
+    Count,
+    Sum,
+    Mean,
+    Stddev,
+    Min,
+    Max,
+    /// The percentile, from 0 to 100.
+    Percentile(f64),
+}
+
+impl Stat {
+    fn of(self, values: &[f64]) -> f64 {
+        let stats = Stats::from_values(values);
+        let stat = |f: fn(&Stats) -> f64| stats.as_ref().map_or(f64::NAN, f);
+        match self {
+            Stat::Count => values.len() as f64,
+            Stat::Sum => values.iter().sum(),
+            Stat::Mean => stat(|stats| stats.mean),
+            Stat::Stddev => stat(|stats| stats.stddev),
+            Stat::Min => stat(|stats| stats.min),
+            Stat::Max => stat(|stats| stats.max),
+            Stat::Percentile(p) => analysis::quantile(values, p / 100.0).unwrap_or(f64::NAN),
+        }
+    }
+
+    /// The statistic of `(value, weight)` pairs, each value counting as much as its weight.
+    fn of_weighted(self, values: &[(f64, f64)]) -> f64 {
+        let stats = Stats::from_weighted_values(values);
+        let stat = |f: fn(&Stats) -> f64| stats.as_ref().map_or(f64::NAN, f);
+        match self {
+            Stat::Count => values.iter().map(|&(_, weight)| weight).sum(),
+            Stat::Sum => values.iter().map(|&(value, weight)| value * weight).sum(),
+            Stat::Mean => stat(|stats| stats.mean),
+            Stat::Stddev => stat(|stats| stats.stddev),
+            Stat::Min => stat(|stats| stats.min),
+            Stat::Max => stat(|stats| stats.max),
+            Stat::Percentile(p) => {
+                analysis::weighted_quantile(values, p / 100.0).unwrap_or(f64::NAN)
+            }
+        }
+    }
+}
+
+#[derive(Args, Clone, Debug)]
+struct InputArgs {
+    /// The optional buffer to read data from.
+    input: Option<PathBuf>,
+
+    /// The zero indexed column in the input buffer to read.
+    #[arg(short, long, group = "source", visible_alias = "value-column")]
+    column: Option<u32>,
+
+    /// The zero indexed column holding how many times each row's value occurred.
+    ///
+    /// For pre-aggregated input, e.g. a `GROUP BY` export of value and count. Counts must be
+    /// whole and non-negative; other rows are malformed, as `--errors-out` records them.
+    #[arg(long, requires = "column")]
+    count_column: Option<u32>,
+
+    /// A regex with one capture group applied to `--column`, whose capture is parsed as the
+    /// value instead of the whole column, e.g. `'(\d+)ms'`.
+    ///
+    /// Rows the regex does not match are skipped.
+    #[arg(long, value_name = "REGEX", requires = "column", value_parser = parse_extract)]
+    extract: Option<Regex>,
+
+    /// A lookup file whose `--map-key` column values replace matching `--column` values with
+    /// the row's `--map-value`, e.g. to weight status codes before binning.
+    ///
+    /// Every `--column` value, after any `--extract`, must appear in the map file, which uses
+    /// the same `--delim`.
+    #[arg(long, requires = "column")]
+    map: Option<PathBuf>,
+
+    /// The zero indexed column of the map file holding the keys.
+    #[arg(long, default_value_t = 0, requires = "map")]
+    map_key: u32,
+
+    /// The zero indexed column of the map file holding the values looked up.
+    #[arg(long, default_value_t = 1, requires = "map")]
+    map_value: u32,
+
+    /// Remember the values of up to this many recently seen distinct `--column` or `--expr`
+    /// fields, so repeated values skip parsing and evaluation.
+    #[arg(long, value_name = "N", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    cache_size: Option<usize>,
+
+    /// The expression over column indices used to compute histogram values.
+    ///
+    /// The `?` prefixes a column index in an expression.
+    /// The expression is evaluated on each row.
+    /// Any `?`ed column indices are bound to a concrete value for each row.
+    #[arg(short, long, group = "source")]
+    expr: Option<String>,
+
+    /// A regex matched against whole input lines instead of splitting them into columns.
+    ///
+    /// Each named capture, e.g. `(?P<ms>\d+)`, is bound as a variable in `--expr`. Without
+    /// `--expr` the `--field` capture, or else the first, is the value. Lines the regex does not
+    /// match are skipped.
+    #[arg(
+        long,
+        value_name = "REGEX",
+        value_parser = parse_pattern,
+        conflicts_with_all = ["column", "diff", "ratio"]
+    )]
+    pattern: Option<Regex>,
+
+    /// A standard log layout to read fields from by name, like a built in `--pattern`.
+    #[arg(long, value_enum, conflicts_with_all = ["column", "diff", "ratio", "pattern"])]
+    preset: Option<Preset>,
+
+    /// The named capture of `--pattern` or `--preset` to bin, instead of the first one.
+    #[arg(long, conflicts_with = "expr")]
+    field: Option<String>,
+
+    /// The pair of column indices whose per-row difference `A - B` is binned.
+    ///
+    /// Equivalent to `--expr '?A - ?B'` without the expression evaluator.
+    #[arg(long, group = "source", value_name = "COL_A,COL_B", value_parser = parse_column_pair)]
+    diff: Option<(u32, u32)>,
+
+    /// The pair of column indices whose per-row ratio `A / B` is binned.
+    #[arg(long, group = "source", value_name = "COL_A,COL_B", value_parser = parse_column_pair)]
+    ratio: Option<(u32, u32)>,
+
+    /// How `--ratio` treats rows whose denominator is within `--zero-epsilon` of zero.
+    #[arg(long, value_enum, default_value_t = ZeroPolicy::Skip, requires = "ratio")]
+    zero_policy: ZeroPolicy,
+
+    /// The magnitude at or below which a `--ratio` denominator counts as zero.
+    #[arg(long, default_value_t = 1e-12, requires = "ratio")]
+    zero_epsilon: f64,
+
+    /// The delimeting pattern used to separate columns in the input.
+    #[arg(short, long, default_value = ",")]
+    delim: String,
+
+    /// Indicate whether the input data contains a header row.
+    #[arg(short, long, default_value_t = false)]
+    skip_header: bool,
+
+    /// The byte separating input records, e.g. `\0` for `find -print0` output or `\r` for old
+    /// Mac files, as a single character or one of the escapes `\0`, `\n`, `\r` and `\t`.
+    #[arg(long, value_name = "SEP", default_value = "\\n", value_parser = parse_record_sep)]
+    record_sep: u8,
+
+    /// Report the rows read per second and in total on stderr every second while reading,
+    /// for keeping an eye on long running ingests.
+    #[arg(long, default_value_t = false, conflicts_with = "from_spill")]
+    progress: bool,
+
+    /// Write each skipped or malformed row to this file as a JSON line with its line number,
+    /// text and error kind, instead of stopping at the first malformed row.
+    #[arg(long, value_name = "PATH")]
+    errors_out: Option<PathBuf>,
+
+    /// Also write every parsed value to this file as raw little-endian `f64`s.
+    #[arg(long)]
+    spill: Option<PathBuf>,
+
+    /// Read values from a `--spill` file instead of parsing input.
+    ///
+    /// Re-binning a large input with a different `--num-bins` this way skips parsing it again.
+    #[arg(long, conflicts_with_all = ["input", "source", "spill", "skip_header", "errors_out"])]
+    from_spill: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct HistogramArgs {
+    /// The optional path to write histogram data to.
+    #[arg(
+        short,
+        long,
+        required_if_eq_any([("format_out", "parquet"), ("format_out", "sqlite")])
+    )]
+    output: Option<PathBuf>,
+
+    /// The number of bins in the histogram.
+    #[arg(short, long, default_value_t = 10)]
+    num_bins: usize,
+
+    /// Pick the number of bins from the values with a rule of thumb instead of `--num-bins`.
+    #[arg(long, value_enum, value_name = "RULE", conflicts_with_all = ["num_bins", "edges", "discrete"])]
+    auto_bins: Option<AutoBins>,
+
+    /// Bin boundaries to count values between instead of `--num-bins` even bins, e.g.
+    /// `0,1,5,10,100`, or the path of a JSON file of them such as `--export-edges` writes.
+    /// Values outside them are not counted.
+    #[arg(
+        long,
+        value_name = "EDGES",
+        value_parser = parse_edges,
+        allow_negative_numbers = true,
+        conflicts_with = "num_bins"
+    )]
+    edges: Option<Edges>,
+
+    /// Also write the edges the values were binned on to this file, as a JSON `{"edges": [..]}`
+    /// object, so `--edges` can bin later datasets exactly as this one.
+    #[arg(
+        long,
+        value_name = "PATH",
+        conflicts_with_all = ["categorical", "group_by_file", "group_by", "kde"]
+    )]
+    export_edges: Option<PathBuf>,
+
+    /// Bins this wide instead of `--num-bins` bins, with edges at multiples of it from
+    /// `--origin`, e.g. `5` for bins 0 to 5, 5 to 10 and so on.
+    #[arg(
+        long,
+        value_name = "WIDTH",
+        conflicts_with_all = ["num_bins", "auto_bins", "edges", "range", "log_bins"]
+    )]
+    bin_width: Option<f64>,
+
+    /// The value `--bin-width` edges are aligned to.
+    #[arg(
+        long,
+        default_value_t = 0.0,
+        allow_negative_numbers = true,
+        requires = "bin_width"
+    )]
+    origin: f64,
+
+    /// Count each distinct value in its own bin instead of binning ranges of values.
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["num_bins", "edges", "range", "log_bins", "bin_width"]
+    )]
+    discrete: bool,
+
+    /// Bin each of these inputs on shared edges as its own group, labelled by its file name,
+    /// e.g. to compare `run1.csv run2.csv run3.csv` without a group column.
+    ///
+    /// Text output has a column of counts per file; `--format-out rhst` saves each file as a
+    /// group under the histogram of all of them.
+    #[arg(
+        long,
+        value_name = "FILE",
+        num_args = 1..,
+        conflicts_with_all = [
+            "input", "from_spill", "spill", "discrete", "log_bins", "cumulative", "keep_samples",
+            "smooth", "flag_anomalies", "test", "thresholds", "descending", "categorical",
+            "assertions"
+        ]
+    )]
+    group_by_file: Vec<PathBuf>,
+
+    /// Bin the groups of rows sharing a value in this zero indexed column on the same edges,
+    /// reading the input once, and write them as `--group-by-file` writes its files.
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        conflicts_with_all = [
+            "group_by_file", "from_spill", "spill", "discrete", "log_bins", "cumulative",
+            "keep_samples", "smooth", "flag_anomalies", "test", "thresholds", "descending",
+            "categorical", "assertions"
+        ]
+    )]
+    group_by: Option<u32>,
+
+    /// A file naming bins for text, JSON and template output, e.g. `fast` or `slow`, in place
+    /// of their numeric labels.
+    ///
+    /// Each line is a zero indexed bin or an inclusive `FIRST-LAST` range of bins, then the
+    /// name, e.g. `0 fast` or `1-3 ok`. Later lines win where they overlap, and blank lines and
+    /// lines starting with `#` are skipped.
+    #[arg(long, value_name = "PATH", value_parser = read_bin_labels)]
+    bin_labels: Option<BinLabels>,
+
+    /// Count each distinct text value of `--column` instead of binning numbers, writing the
+    /// most frequent first.
+    #[arg(
+        long,
+        default_value_t = false,
+        requires = "column",
+        conflicts_with_all = [
+            "num_bins", "edges", "bin_width", "discrete", "log_bins", "range", "format_out", "test",
+            "smooth",
+            "flag_anomalies", "keep_samples", "density", "cumulative", "sig_figs", "descending",
+            "label_units", "thresholds", "map", "from_spill", "assertions"
+        ]
+    )]
+    categorical: bool,
+
+    /// HdrHistogram style bins instead of `--num-bins`: each power of `--hdr-base` split into
+    /// even bins that tell values apart to this many significant digits, 2 if not given.
+    ///
+    /// For latencies, with fine bins near zero and coarse ones in the tail. Negative values
+    /// are not counted.
+    #[arg(
+        long,
+        value_name = "DIGITS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "2",
+        conflicts_with_all = [
+            "num_bins", "auto_bins", "edges", "bin_width", "discrete", "range", "log_bins",
+            "memory_limit", "tdigest"
+        ]
+    )]
+    hdr: Option<u32>,
+
+    /// The base whose powers `--hdr` splits into bins.
+    #[arg(long, default_value_t = 2.0, requires = "hdr")]
+    hdr_base: f64,
+
+    /// Space the `--num-bins` bins geometrically between the smallest and largest positive
+    /// values, leaving out zero and negative values.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["edges", "range"])]
+    log_bins: bool,
+
+    /// The range `--num-bins` even bins span instead of the range of the values, so runs over
+    /// different inputs bin identically.
+    #[arg(
+        long,
+        value_name = "MIN,MAX",
+        value_parser = parse_range,
+        allow_hyphen_values = true,
+        conflicts_with = "edges"
+    )]
+    range: Option<(f64, f64)>,
+
+    /// Write rows counting the values below and above `--range` or `--edges`, labelled `<MIN`
+    /// and `>MAX`, around the text bins.
+    #[arg(long, default_value_t = false)]
+    flow_bins: bool,
+
+    /// What `--range` does with values outside it.
+    #[arg(long, value_enum, default_value_t = OutOfRange::Drop, requires = "range")]
+    out_of_range: OutOfRange,
+
+    /// What happens to NaN and infinite values, other than the infinities counted by
+    /// `--zero-policy infinity`.
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = NonFinite::Skip)]
+    nan: NonFinite,
+
+    /// The format to write the histogram in.
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    format_out: Format,
+
+    /// Mark on the `--format-out chart` bars what each bin would hold of a `normal`
+    /// distribution with the values' mean and standard deviation, of a `uniform` one over the
+    /// bins, or of a saved `.rhst` histogram with the same bins, out of as many values.
+    #[arg(
+        long,
+        value_name = "normal|uniform|FILE",
+        value_parser = parse_overlay,
+        conflicts_with_all = ["categorical", "group_by_file", "group_by", "kde"]
+    )]
+    overlay: Option<Overlay>,
+
+    /// Scale the `--format-out chart` bars by the logarithm of their counts, so bins many orders
+    /// of magnitude below the fullest are still drawn.
+    #[arg(long, conflicts_with_all = ["categorical", "group_by_file", "group_by", "kde"])]
+    log_y: bool,
+
+    /// A title line above the `--format-out chart` bars.
+    #[arg(long, conflicts_with_all = ["categorical", "group_by_file", "group_by", "kde"])]
+    title: Option<String>,
+
+    /// What the bin labels measure, written over them in `--format-out chart`.
+    #[arg(long, conflicts_with_all = ["categorical", "group_by_file", "group_by", "kde"])]
+    xlabel: Option<String>,
+
+    /// What the counts measure, written over the bars in `--format-out chart`.
+    #[arg(long, conflicts_with_all = ["categorical", "group_by_file", "group_by", "kde"])]
+    ylabel: Option<String>,
+
+    /// Mark the `--format-out chart` bins holding these quantiles, from 0 to 1, with each
+    /// quantile's value as estimated from the bins, e.g. `0.5,0.95,0.99`.
+    #[arg(
+        long,
+        value_name = "QUANTILES",
+        value_delimiter = ',',
+        value_parser = parse_quantile,
+        conflicts_with_all = ["categorical", "group_by_file", "group_by", "kde", "cumulative"]
+    )]
+    mark_quantiles: Vec<f64>,
+
+    /// A minijinja template rendered with the histogram's `bins`, `stats` and metadata instead
+    /// of writing `--format-out`.
+    #[cfg(feature = "template")]
+    #[arg(long, conflicts_with_all = ["group_by_file", "group_by"])]
+    template: Option<PathBuf>,
+
+    /// Leave the input name, command line, row count and timestamp out of the output.
+    #[arg(long, default_value_t = false)]
+    no_metadata: bool,
+
+    /// Also PUT the histogram to this Prometheus Pushgateway URL, e.g.
+    /// `http://pushgateway:9091/metrics/job/rhisto`, for batch jobs to skip being scraped.
+    #[arg(long, value_name = "URL")]
+    push: Option<String>,
+
+    /// The metric name `--push` publishes the histogram under.
+    #[arg(long, default_value = "rhisto", requires = "push")]
+    push_metric: String,
+
+    /// The table `--format-out sqlite` inserts bins into, created if missing.
+    #[cfg(feature = "sqlite")]
+    #[arg(long, default_value = "histograms")]
+    table: String,
+
+    /// The tag stored with each bin by `--format-out sqlite`, identifying the run.
+    #[cfg(feature = "sqlite")]
+    #[arg(long)]
+    tag: Option<String>,
+
+    /// Exit with status 1 unless this statistic of the binned values meets a bound, e.g.
+    /// `'p99 < 250'` or `'count > 1000'`, to gate CI jobs. May be given more than once.
+    ///
+    /// The statistic is one `trend --stat` takes, compared by `<`, `<=`, `==`, `!=`, `>=` or
+    /// `>`. Each broken assertion is reported on stderr.
+    #[arg(long = "assert", value_name = "ASSERTION", value_parser = parse_assertion)]
+    assertions: Vec<Assertion>,
+
+    /// A statistical test to run on the values and report after the histogram.
+    #[arg(long, value_enum)]
+    test: Option<Test>,
+
+    /// Write a Gaussian kernel density estimate at this many evenly spaced points instead of
+    /// bin counts, as `x<delim>density` rows, for a smooth curve that does not depend on the
+    /// number of bins.
+    #[arg(
+        long,
+        value_name = "POINTS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100",
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        conflicts_with_all = [
+            "categorical", "group_by_file", "group_by", "memory_limit", "format_out", "assertions"
+        ]
+    )]
+    kde: Option<usize>,
+
+    /// The `--kde` kernels' standard deviation, instead of picking one by Silverman's rule.
+    #[arg(long, requires = "kde")]
+    bandwidth: Option<f64>,
+
+    /// Add a column of counts smoothed by a Gaussian with this standard deviation, in bins.
+    #[arg(long, value_name = "SIGMA")]
+    smooth: Option<f64>,
+
+    /// Mark bins whose count spikes or dips relative to their neighbors with a trailing `*`.
+    #[arg(long, default_value_t = false)]
+    flag_anomalies: bool,
+
+    /// The number of standard deviations from the neighboring baseline that flags a bin.
+    #[arg(long, default_value_t = 3.0, requires = "flag_anomalies")]
+    anomaly_sigma: f64,
+
+    /// Keep up to this many of the raw input rows in each bin, chosen at random, and list them
+    /// as `samples` in `--format-out json`.
+    #[arg(long, value_name = "K", conflicts_with = "from_spill")]
+    keep_samples: Option<usize>,
+
+    /// Bin in one pass through a t-digest sketch of about this many centroids, 100 if not
+    /// given, instead of holding every value, so unbounded input takes bounded memory.
+    ///
+    /// Bin counts are approximate, each centroid counted at its mean. Without the edges known
+    /// ahead of the values, `--nan clamp-to-edges` leaves infinities out.
+    #[arg(
+        long,
+        value_name = "COMPRESSION",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "100",
+        value_parser = parse_compression,
+        conflicts_with_all = [
+            "memory_limit", "auto_bins", "edges", "bin_width", "discrete", "log_bins", "range",
+            "keep_samples", "test", "categorical", "group_by_file", "group_by", "spill",
+            "from_spill", "assertions", "kde"
+        ]
+    )]
+    tdigest: Option<f64>,
+
+    /// Once the parsed values would take more than this much memory, e.g. `512M` or `2G`,
+    /// move them to a temporary file and bin them in a second pass over it instead.
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        conflicts_with_all = [
+            "auto_bins", "edges", "bin_width", "discrete", "log_bins", "keep_samples", "test",
+            "categorical", "group_by_file", "group_by", "spill", "from_spill", "assertions"
+        ]
+    )]
+    memory_limit: Option<u64>,
+
+    /// Keep the input's range and value count in this file, keyed by the input file and the
+    /// options that pick its values, so later `--memory-limit` runs on an unchanged input bin it
+    /// in one pass instead of spilling it first to find the range.
+    #[arg(long, value_name = "FILE", requires_all = ["memory_limit", "input"])]
+    cache: Option<PathBuf>,
+
+    /// Write the exact value of each of these quantiles, from 0 to 1, after the text histogram.
+    ///
+    /// With `--memory-limit` the values are sorted in runs that fit in the limit, written to
+    /// `--tmpdir` and merged, so the quantiles stay exact however large the input.
+    #[arg(
+        long,
+        value_name = "QUANTILES",
+        value_delimiter = ',',
+        value_parser = parse_quantile,
+        conflicts_with_all = [
+            "weight_column", "tdigest", "categorical", "group_by_file", "group_by", "kde",
+            "cumulative"
+        ]
+    )]
+    exact_quantiles: Vec<f64>,
+
+    /// The directory for `--memory-limit` spill files and `--exact-quantiles` sorted runs,
+    /// instead of the system's temporary directory.
+    #[arg(long, value_name = "DIR", requires = "memory_limit")]
+    tmpdir: Option<PathBuf>,
+
+    /// The zero indexed column holding each row's weight, e.g. a survey sampling weight, which
+    /// need not be whole.
+    ///
+    /// Each value then counts as much as its weight in the bins, the summary statistics and
+    /// `--assert`ions, percentiles included. Weights must be non-negative numbers.
+    #[arg(
+        long,
+        value_name = "COLUMN",
+        requires = "column",
+        conflicts_with_all = [
+            "count_column", "tdigest", "memory_limit", "auto_bins", "edges", "bin_width",
+            "discrete", "log_bins", "hdr", "keep_samples", "test", "thresholds", "categorical",
+            "group_by_file", "group_by", "kde", "spill", "from_spill"
+        ]
+    )]
+    weight_column: Option<u32>,
+
+    /// Write each count as a fraction of the total in text and literal output, so inputs of
+    /// different sizes compare.
+    ///
+    /// With `--group-by-file` or `--group-by` the total is that of every group, unless
+    /// `--normalize=within-group` makes each group's counts fractions of its own total instead.
+    #[arg(
+        long,
+        value_enum,
+        value_name = "TOTAL",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "all",
+        conflicts_with_all = ["density", "cumulative"]
+    )]
+    normalize: Option<Normalize>,
+
+    /// Write each count divided by its bin's width in text and literal output.
+    #[arg(long, default_value_t = false, conflicts_with = "cumulative")]
+    density: bool,
+
+    /// Write running totals of the counts, for a CDF-style table.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["flag_anomalies", "smooth"])]
+    cumulative: bool,
+
+    /// Write text bin labels to this many significant digits instead of two decimal places.
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..=17),
+        conflicts_with = "label_units"
+    )]
+    sig_figs: Option<u32>,
+
+    /// Write text bins from the highest values to the lowest.
+    #[arg(long, default_value_t = false)]
+    descending: bool,
+
+    /// Write text bin labels with unit suffixes, e.g. `1.2 MiB`, `350ms` or `4.5k`.
+    #[arg(long, value_enum)]
+    label_units: Option<LabelUnits>,
+
+    /// Thresholds to report the count and fraction of values exceeding, after the histogram.
+    #[arg(long, value_delimiter = ',', allow_negative_numbers = true)]
+    thresholds: Vec<f64>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Format {
+    /// One `label<delim>count` row per bin.
+    Text,
+    /// A bar chart of the counts, with any `--overlay` marked on each bar.
+    Chart,
+    /// `let edges = [..]; let counts = [..];` array literals.
+    Rust,
+    /// `edges = [..]` and `counts = [..]` list literals.
+    Python,
+    /// `edges` and `counts` as `np.array` literals.
+    Numpy,
+    /// A saved `.rhst` histogram with its statistics and metadata.
+    Rhst,
+    /// A JSON object with a `bins` array, each bin with its `label`, `lower` and `upper`
+    /// edges, `count` and any `--keep-samples` rows, and the `underflow` and `overflow` counts.
+    ///
+    /// With `--group-by-file` or `--group-by`, one object bundling every histogram: the `total`
+    /// of all groups and a `groups` array of each group's histogram along with its `name`.
+    Json,
+    /// The same contents as `rhst`, serialized as compact CBOR.
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// `lower`, `upper`, `count` and `density` columns in a Parquet file.
+    #[cfg(feature = "parquet")]
+    Parquet,
+    /// `tag`, `lower`, `upper` and `count` rows inserted into a SQLite database.
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+/// What `--overlay` marks on a chart.
+#[derive(Clone, Debug, PartialEq)]
+enum Overlay {
+    Normal,
+    Uniform,
+    /// The counts of a saved `.rhst` histogram.
+    Histogram(PathBuf),
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Test {
+    /// Anderson–Darling test for normality; needs at least eight values.
+    Normality,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Normalize {
+    /// Divide by the total of every count.
+    All,
+    /// Divide each `--group-by-file` or `--group-by` group's counts by the group's own total.
+    WithinGroup,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ZeroPolicy {
+    /// Drop the row.
+    Skip,
+    /// Divide by the epsilon instead, keeping the sign of the denominator.
+    Clamp,
+    /// Count the row in a separate `-inf` or `inf` bin either side of the histogram, by the
+    /// sign of the ratio. `0/0` has no sign, so it is dropped.
+    Infinity,
+}
+
+/// `value` rounded to `sig_figs` significant digits, without trailing noise digits.
+fn format_sig_figs(value: f64, sig_figs: u32) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return value.to_string();
+    }
+
+    let magnitude = value.abs().log10().floor() as i32;
+    let factor = 10f64.powi(magnitude + 1 - sig_figs as i32);
+    let rounded = (value / factor).round() * factor;
+    // Rounding up can carry into the next magnitude, as 9.996 does to 10.0.
+    let magnitude = rounded.abs().log10().floor() as i32;
+    let decimals = (sig_figs as i32 - 1 - magnitude).max(0) as usize;
+    format!("{:.*}", decimals, rounded)
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum LabelUnits {
+    /// A byte count, with binary prefixes.
+    Bytes,
+    /// A number of seconds.
+    Duration,
+    /// A plain number, with SI prefixes.
+    Si,
+}
+
+impl LabelUnits {
+    fn format(self, value: f64) -> String {
+        let (scale, suffix) = match self {
+            LabelUnits::Bytes => [
+                (1u64 << 40, " TiB"),
+                (1 << 30, " GiB"),
+                (1 << 20, " MiB"),
+                (1 << 10, " KiB"),
+            ]
+            .into_iter()
+            .map(|(scale, suffix)| (scale as f64, suffix))
+            .find(|&(scale, _)| value.abs() >= scale)
+            .unwrap_or((1.0, " B")),
+            LabelUnits::Duration => [
+                (3600.0, "h"),
+                (60.0, "m"),
+                (1.0, "s"),
+                (1e-3, "ms"),
+                (1e-6, "µs"),
+            ]
+            .into_iter()
+            .find(|&(scale, _)| value.abs() >= scale)
+            .unwrap_or((1e-9, "ns")),
+            LabelUnits::Si => [(1e12, "T"), (1e9, "G"), (1e6, "M"), (1e3, "k")]
+                .into_iter()
+                .find(|&(scale, _)| value.abs() >= scale)
+                .unwrap_or((1.0, "")),
+        };
+
+        let scaled = format!("{:.1}", value / scale);
+        let scaled = scaled.strip_suffix(".0").unwrap_or(&scaled);
+        format!("{}{}", scaled, suffix)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum Preset {
+    /// nginx's `combined` access log, optionally followed by `$request_time`.
+    ///
+    /// Fields: `status`, `bytes` and `request_time` (seconds).
+    NginxAccess,
+    /// Apache's `combined` access log, optionally followed by `%D`.
+    ///
+    /// Fields: `status`, `bytes` and `duration_us` (microseconds).
+    ApacheCombined,
+    /// `journalctl -o json` entries, one JSON object a line.
+    ///
+    /// Fields: any numeric one of the entry's, `PRIORITY` by default. `trend` takes the time
+    /// from `__REALTIME_TIMESTAMP` without a `--time-column`.
+    JournalJson,
+}
+
+impl Preset {
+    /// The pattern of the preset's lines, or `None` for JSON presets read field by field.
+    fn regex(self) -> Option<Regex> {
+        // `%h %l %u %t "%r" %>s %b "%{Referer}i" "%{User-agent}i"` in both, a `-` size being
+        // an empty response.
+        let combined = r#"^\S+ \S+ \S+ \[[^\]]*\] "[^"]*" (?P<status>\d{3}) (?:(?P<bytes>\d+)|-) "[^"]*" "[^"]*""#;
+        let pattern = match self {
+            Preset::NginxAccess => format!(r"{}(?: (?P<request_time>[\d.]+))?", combined),
+            Preset::ApacheCombined => format!(r"{}(?: (?P<duration_us>\d+))?", combined),
+            Preset::JournalJson => return None,
+        };
+
+        Some(Regex::new(&pattern).expect("presets are valid regexes"))
+    }
+}
+
+/// The text of the top level `name` field of a one line JSON object, unquoted if a string.
+///
+/// journald entries are flat objects of strings, so this skips a full JSON parser. Escapes in
+/// the value are left as they are, which numbers and timestamps never have.
+fn journal_field<'a>(entry: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("\"{}\"", name);
+    let mut from = 0;
+    while let Some(at) = entry[from..].find(&key).map(|at| from + at) {
+        from = at + key.len();
+        // A key inside another field's string has its quotes escaped.
+        if entry[..at].ends_with('\\') {
+            continue;
+        }
+        let Some(value) = entry[from..].trim_start().strip_prefix(':') else {
+            continue;
+        };
+
+        let value = value.trim_start();
+        return match value.strip_prefix('"') {
+            Some(string) => string.find('"').map(|end| &string[..end]),
+            None => Some(value[..value.find([',', '}']).unwrap_or(value.len())].trim_end()),
+        };
+    }
+
+    None
+}
+
+/// The Unix seconds of a journald entry, from its `__REALTIME_TIMESTAMP` microseconds.
+fn journal_time(entry: &str) -> Result<f64, RowError> {
+    let micros =
+        journal_field(entry, "__REALTIME_TIMESTAMP").ok_or(RowError::Skipped("missing_time"))?;
+    micros
+        .parse::<u64>()
+        .map(|micros| micros as f64 / 1e6)
+        .map_err(|_| RowError::Failed("bad_time", format!("`{}` is not a time", micros)))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum OutOfRange {
+    /// Leave them out of the histogram.
+    Drop,
+    /// Count them in the first or last bin.
+    Clip,
+}
+
+impl From<OutOfRange> for rhisto::OutOfRange {
+    fn from(out_of_range: OutOfRange) -> Self {
+        match out_of_range {
+            OutOfRange::Drop => rhisto::OutOfRange::Drop,
+            OutOfRange::Clip => rhisto::OutOfRange::Clip,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum NonFinite {
+    /// Leave them out of the histogram.
+    Skip,
+    /// Stop with an error.
+    Error,
+    /// Count infinities in the first or last bin, leaving NaN out.
+    ClampToEdges,
+}
+
+impl From<NonFinite> for NonFinitePolicy {
+    fn from(non_finite: NonFinite) -> Self {
+        match non_finite {
+            NonFinite::Skip => NonFinitePolicy::Skip,
+            NonFinite::Error => NonFinitePolicy::Error,
+            NonFinite::ClampToEdges => NonFinitePolicy::ClampToEdges,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum AutoBins {
+    /// `log2(n) + 1` bins.
+    Sturges,
+    /// Bins `3.49 * stddev / cbrt(n)` wide.
+    Scott,
+    /// Bins `2 * IQR / cbrt(n)` wide, which suits skewed values and outliers.
+    FreedmanDiaconis,
+    /// `2 * cbrt(n)` bins.
+    Rice,
+}
+
+impl From<AutoBins> for rhisto::AutoBins {
+    fn from(rule: AutoBins) -> Self {
+        match rule {
+            AutoBins::Sturges => rhisto::AutoBins::Sturges,
+            AutoBins::Scott => rhisto::AutoBins::Scott,
+            AutoBins::FreedmanDiaconis => rhisto::AutoBins::FreedmanDiaconis,
+            AutoBins::Rice => rhisto::AutoBins::Rice,
+        }
+    }
+}
+
+fn parse_range(s: &str) -> Result<(f64, f64), String> {
+    let (min, max) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected `MIN,MAX` but found `{}`", s))?;
+    let parse = |bound: &str| {
+        bound
+            .trim()
+            .parse::<f64>()
+            .map_err(|_| format!("`{}` is not a number", bound))
+    };
+
+    let (min, max) = (parse(min)?, parse(max)?);
+    if min >= max {
+        return Err(format!("MIN must be below MAX but found `{}`", s));
+    }
+
+    Ok((min, max))
+}
+
+fn parse_quantile(s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(q) if (0.0..=1.0).contains(&q) => Ok(q),
+        _ => Err(format!("`{}` is not a quantile from 0 to 1", s)),
+    }
+}
+
+fn parse_compression(s: &str) -> Result<f64, String> {
+    let compression = s
+        .parse::<f64>()
+        .map_err(|_| format!("`{}` is not a number", s))?;
+    TDigest::new(compression)
+        .map(|_| compression)
+        .map_err(|err| err.to_string())
+}
+
+/// Parses a number of bytes, with an optional `K`, `M`, `G` or `T` suffix counting in 1024s.
+fn parse_size(s: &str) -> Result<u64, String> {
+    let (number, shift) = match s.char_indices().last() {
+        Some((i, suffix)) if suffix.is_ascii_alphabetic() => {
+            let shift = match suffix.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(format!("unknown size suffix in `{}`", s)),
+            };
+            (&s[..i], shift)
+        }
+        _ => (s, 0),
+    };
+    let number = number
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite() && *number >= 0.0)
+        .ok_or_else(|| format!("`{}` is not a size", s))?;
+
+    Ok((number * (1u64 << shift) as f64) as u64)
+}
+
+/// Bin boundaries given to `--edges`, inline or read from a JSON file.
+#[derive(Debug, Clone, PartialEq)]
+struct Edges(Vec<f64>);
+
+impl std::ops::Deref for Edges {
+    type Target = [f64];
+
+    fn deref(&self) -> &[f64] {
+        &self.0
+    }
+}
+
+/// Comma separated edges, or else the path of a JSON file of them, ascending through at
+/// least two values.
+fn parse_edges(s: &str) -> Result<Edges, String> {
+    let edges: Vec<f64> = match s.split(',').map(|edge| edge.trim().parse()).collect() {
+        Ok(edges) => edges,
+        Err(_) if !std::path::Path::new(s).is_file() => {
+            return Err(format!(
+                "`{}` is neither comma separated numbers nor an edges file",
+                s
+            ));
+        }
+        Err(_) => {
+            let json = std::fs::read_to_string(s)
+                .map_err(|err| format!("failed to read `{}`: {}", s, err))?;
+            read_edges_json(&json).map_err(|err| format!("`{}`: {}", s, err))?
+        }
+    };
+
+    if edges.len() < 2 {
+        return Err(format!("`{}` needs at least two edges to bound a bin", s));
+    }
+    match edges
+        .windows(2)
+        .find(|pair| pair[0].partial_cmp(&pair[1]) != Some(std::cmp::Ordering::Less))
+    {
+        Some(pair) => Err(format!("edge {} does not ascend from {}", pair[1], pair[0])),
+        None => Ok(Edges(edges)),
+    }
+}
+
+/// The edges in a `{"edges": [..]}` object or a bare JSON array of numbers.
+fn read_edges_json(json: &str) -> Result<Vec<f64>, String> {
+    let json = json.trim();
+    let array = match json.strip_prefix('{') {
+        Some(object) => {
+            let key = object.find("\"edges\"").ok_or("expected an `edges` key")?;
+            object[key + "\"edges\"".len()..]
+                .trim_start()
+                .strip_prefix(':')
+                .ok_or("expected `:` after `edges`")?
+        }
+        None => json,
+    };
+    let array = array
+        .trim_start()
+        .strip_prefix('[')
+        .ok_or("expected an array of edges")?;
+    let end = array.find(']').ok_or("unterminated array of edges")?;
+
+    array[..end]
+        .split(',')
+        .map(str::trim)
+        .filter(|edge| !edge.is_empty())
+        .map(|edge| {
+            edge.parse()
+                .map_err(|_| format!("`{}` is not a number", edge))
+        })
+        .collect()
+}
+
+/// `edges` as the JSON object `--export-edges` writes and `--edges` reads.
+fn edges_json(edges: &[f64]) -> String {
+    let edges: Vec<String> = edges.iter().map(|&edge| json_number(edge)).collect();
+    format!("{{\"edges\":[{}]}}\n", edges.join(","))
+}
+
+/// Names for ranges of bins, read from a `--bin-labels` file.
+#[derive(Debug, Clone, PartialEq)]
+struct BinLabels(Vec<(usize, usize, String)>);
+
+impl BinLabels {
+    /// The name of the `i`th bin, from the last line covering it.
+    fn get(&self, i: usize) -> Option<&str> {
+        self.0
+            .iter()
+            .rev()
+            .find(|(first, last, _)| (*first..=*last).contains(&i))
+            .map(|(_, _, name)| name.as_str())
+    }
+}
+
+impl std::str::FromStr for BinLabels {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut names = Vec::new();
+        for (n, line) in s.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let malformed = || format!("line {}: expected `INDEX NAME` or `FIRST-LAST NAME`", n);
+            let (bins, name) = line.split_once(char::is_whitespace).ok_or_else(malformed)?;
+            let (first, last) = bins.split_once('-').unwrap_or((bins, bins));
+            let (first, last) = match (first.parse(), last.parse()) {
+                (Ok(first), Ok(last)) if first <= last => (first, last),
+                _ => return Err(malformed()),
+            };
+            names.push((first, last, name.trim().to_string()));
+        }
+
+        Ok(Self(names))
+    }
+}
+
+fn read_bin_labels(path: &str) -> Result<BinLabels, String> {
+    std::fs::read_to_string(path)
+        .map_err(|err| format!("failed to read `{}`: {}", path, err))?
+        .parse()
+}
+
+/// Parses a number of seconds, with an optional `s`, `m`, `h`, `d` or `w` suffix.
+fn parse_duration(s: &str) -> Result<f64, String> {
+    let (number, unit) = match s.char_indices().last() {
+        Some((i, suffix)) if suffix.is_ascii_alphabetic() => {
+            let unit = match suffix {
+                's' => 1.0,
+                'm' => 60.0,
+                'h' => 3600.0,
+                'd' => 86400.0,
+                'w' => 604800.0,
+                _ => return Err(format!("unknown duration suffix in `{}`", s)),
+            };
+            (&s[..i], unit)
+        }
+        _ => (s, 1.0),
+    };
+
+    match number.trim().parse::<f64>() {
+        Ok(number) if number.is_finite() && number > 0.0 => Ok(number * unit),
+        _ => Err(format!("`{}` is not a positive duration", s)),
+    }
+}
+
+fn parse_overlay(s: &str) -> Result<Overlay, String> {
+    Ok(match s {
+        "normal" => Overlay::Normal,
+        "uniform" => Overlay::Uniform,
+        path => Overlay::Histogram(PathBuf::from(path)),
+    })
+}
+
+fn parse_stat(s: &str) -> Result<Stat, String> {
+    Ok(match s {
+        "count" => Stat::Count,
+        "sum" => Stat::Sum,
+        "mean" => Stat::Mean,
+        "stddev" => Stat::Stddev,
+        "min" => Stat::Min,
+        "max" => Stat::Max,
+        "median" => Stat::Percentile(50.0),
+        _ => match s.strip_prefix('p').map(str::parse::<f64>) {
+            Some(Ok(percentile)) if (0.0..=100.0).contains(&percentile) => {
+                Stat::Percentile(percentile)
+            }
+            _ => return Err(format!("`{}` is not a statistic", s)),
+        },
+    })
+}
+
+fn parse_assertion(s: &str) -> Result<Assertion, String> {
+    let (stat, test, bound) =
+        split_comparison(s).ok_or_else(|| format!("`{}` has no comparison", s))?;
+    let bound = bound.trim();
+    Ok(Assertion {
+        text: s.trim().to_string(),
+        stat: parse_stat(stat.trim())?,
+        test,
+        bound: bound
+            .parse()
+            .map_err(|_| format!("`{}` is not a number", bound))?,
+    })
+}
+
+fn parse_record_sep(s: &str) -> Result<u8, String> {
+    match s {
+        "\\0" => Ok(b'\0'),
+        "\\n" => Ok(b'\n'),
+        "\\r" => Ok(b'\r'),
+        "\\t" => Ok(b'\t'),
+        _ if s.len() == 1 => Ok(s.as_bytes()[0]),
+        _ => Err(format!(
+            "expected a single byte separator but found `{}`",
+            s
+        )),
+    }
+}
+
+fn parse_column_pair(s: &str) -> Result<(u32, u32), String> {
+    let (a, b) = s
+        .split_once(',')
+        .ok_or_else(|| format!("expected `COL_A,COL_B` but found `{}`", s))?;
+    let parse = |col: &str| {
+        col.trim()
+            .parse::<u32>()
+            .map_err(|_| format!("`{}` is not a column index", col))
+    };
+
+    Ok((parse(a)?, parse(b)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parse_extract_needs_one_group() {
+        assert!(parse_extract(r"(\d+)ms").is_ok());
+        assert!(parse_extract(r"\d+ms").is_err());
+        assert!(parse_extract(r"(\d+)(ms)").is_err());
+    }
+
+    #[test]
+    fn pattern_binds_named_captures() {
+        let args = Cli::parse_from([
+            "rhisto",
+            "--pattern",
+            r"took (?P<ms>\d+)ms",
+            "-e",
+            "ms / 1000",
+        ]);
+        let extract = extractor(&args.input);
+        assert_eq!(extract("GET / took 250ms"), Ok((0.25, 1)));
+        assert_eq!(
+            extract("no timing here"),
+            Err(RowError::Skipped("no_match"))
+        );
+        assert!(parse_pattern(r"took (\d+)ms").is_err());
+    }
+
+    #[test]
+    fn nginx_preset_reads_fields() {
+        let line = r#"10.0.0.1 - - [10/Oct/2026:13:55:36 +0000] "GET /a HTTP/1.1" 200 512 "-" "curl/8.0" 0.042"#;
+        let args = Cli::parse_from([
+            "rhisto",
+            "--preset",
+            "nginx-access",
+            "--field",
+            "request_time",
+        ]);
+        assert_eq!(extractor(&args.input)(line), Ok((0.042, 1)));
+
+        let args = Cli::parse_from(["rhisto", "--preset", "nginx-access"]);
+        assert_eq!(extractor(&args.input)(line), Ok((200.0, 1)));
+    }
+
+    #[test]
+    fn journal_preset_reads_fields() {
+        let entry = r#"{ "MESSAGE" : "said \"PRIORITY\" : 0", "PRIORITY" : "3", "_PID" : 42, "__REALTIME_TIMESTAMP" : "1714564800500000" }"#;
+        let args = Cli::parse_from(["rhisto", "--preset", "journal-json"]);
+        assert_eq!(extractor(&args.input)(entry), Ok((3.0, 1)));
+        assert_eq!(journal_field(entry, "_PID"), Some("42"));
+        assert_eq!(journal_field(entry, "_UID"), None);
+        assert_eq!(journal_time(entry), Ok(1714564800.5));
+    }
+
+    #[test]
+    fn zero_denominators_keep_their_sign() {
+        let args = Cli::parse_from(["rhisto", "--ratio", "0,1", "--zero-policy", "infinity"]);
+        let ratio = extractor(&args.input);
+        assert_eq!(ratio("-3,0"), Ok((f64::NEG_INFINITY, 1)));
+        assert_eq!(ratio("3,-0"), Ok((f64::NEG_INFINITY, 1)));
+        assert_eq!(ratio("3,0"), Ok((f64::INFINITY, 1)));
+        assert_eq!(ratio("0,0"), Err(RowError::Skipped("zero_over_zero")));
+    }
+
+    #[test]
+    fn counts_must_be_whole() {
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--count-column", "1"]);
+        let counted = extractor(&args.input);
+        assert_eq!(counted("1.5,3"), Ok((1.5, 3)));
+        for row in ["1.5,2.5", "1.5,-1"] {
+            assert!(matches!(
+                counted(row),
+                Err(RowError::Failed("bad_count", _))
+            ));
+        }
+    }
+
+    #[test]
+    fn sig_figs_adapt_to_magnitude() {
+        assert_eq!(format_sig_figs(0.001234, 3), "0.00123");
+        assert_eq!(format_sig_figs(1.234, 3), "1.23");
+        assert_eq!(format_sig_figs(1234.0, 3), "1230");
+        assert_eq!(format_sig_figs(-9.996, 3), "-10.0");
+        assert_eq!(format_sig_figs(0.0, 3), "0");
+    }
+
+    #[test]
+    fn label_units_pick_prefixes() {
+        assert_eq!(LabelUnits::Bytes.format(1.2 * 1024.0 * 1024.0), "1.2 MiB");
+        assert_eq!(LabelUnits::Bytes.format(512.0), "512 B");
+        assert_eq!(LabelUnits::Duration.format(0.35), "350ms");
+        assert_eq!(LabelUnits::Duration.format(90.0), "1.5m");
+        assert_eq!(LabelUnits::Si.format(4500.0), "4.5k");
+        assert_eq!(LabelUnits::Si.format(0.5), "0.5");
+    }
+
+    #[test]
+    fn awk_filter_matches_bin_range() {
+        let edges = [0.0, 2.5, 5.0];
+        assert_eq!(
+            awk_filter(&edges, 0, 1, ","),
+            "awk -F ',' '$2 >= 0 && $2 <= 2.5'"
+        );
+        assert_eq!(
+            awk_filter(&edges, 1, 0, "::"),
+            "awk -F '::' '$1 > 2.5 && $1 <= 5'"
+        );
+    }
+
+    #[test]
+    fn row_errors_are_json_lines() {
+        let mut out = Vec::new();
+        let failed = RowError::Failed("failed_parse", "`x` is not a number".to_string());
+        write_row_error(&mut out, 3, "a,\"x\"", &failed).unwrap();
+        write_row_error(&mut out, 4, "b", &RowError::Skipped("no_match")).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"line\":3,\"row\":\"a,\\\"x\\\"\",\"kind\":\"failed_parse\",\"message\":\"`x` is not a number\"}\n\
+             {\"line\":4,\"row\":\"b\",\"kind\":\"no_match\"}\n"
+        );
+    }
+
+    #[test]
+    fn group_names_fall_back_to_paths() {
+        let paths = [PathBuf::from("a/run1.csv"), PathBuf::from("run2.csv")];
+        assert_eq!(group_names(&paths), vec!["run1.csv", "run2.csv"]);
+
+        let paths = [PathBuf::from("a/run.csv"), PathBuf::from("b/run.csv")];
+        assert_eq!(group_names(&paths), vec!["a/run.csv", "b/run.csv"]);
+    }
+
+    #[test]
+    fn warns_about_likely_mistakes() {
+        let rows = |rows: &[&str]| rows.iter().map(|row| row.to_string()).collect::<Vec<_>>();
+        assert_eq!(warnings(&rows(&["1,2", "3,4"]), ",", Some(1)), vec![]);
+        assert_eq!(warnings(&rows(&["1", "2"]), ",", Some(0)), vec![]);
+        assert_eq!(
+            warnings(&rows(&["1\t2", "3\t4"]), ",", None),
+            vec![Warning::DelimiterNotFound(",".to_string(), '\t')]
+        );
+        assert_eq!(
+            warnings(&rows(&["a,,1", "b,,2"]), ",", Some(1)),
+            vec![Warning::EmptyColumn(1, Some(0))]
+        );
+    }
+
+    #[test]
+    fn bin_labels_cover_ranges() {
+        let labels: BinLabels = "# sla\n0 fast\n1-3 ok\n\n3 slow\n".parse().unwrap();
+        let names: Vec<Option<&str>> = (0..5).map(|i| labels.get(i)).collect();
+        assert_eq!(
+            names,
+            vec![Some("fast"), Some("ok"), Some("ok"), Some("slow"), None]
+        );
+        assert!("3-1 backwards".parse::<BinLabels>().is_err());
+    }
+
+    #[test]
+    fn exported_edges_read_back() {
+        let edges = [-1.5, 0.0, 2.0, 1e-7];
+        assert_eq!(read_edges_json(&edges_json(&edges)), Ok(edges.to_vec()));
+        assert_eq!(read_edges_json(" [1, 2.5 ,4]"), Ok(vec![1.0, 2.5, 4.0]));
+        assert!(read_edges_json(r#"{"bins": [1, 2]}"#).is_err());
+        assert_eq!(parse_edges("0, 5,10"), Ok(Edges(vec![0.0, 5.0, 10.0])));
+        assert!(parse_edges("0,five").is_err());
+        assert_eq!(
+            parse_edges("5,1"),
+            Err("edge 1 does not ascend from 5".to_string())
+        );
+        assert!(parse_edges("5").is_err());
+        assert!(parse_edges("1,nan").is_err());
+    }
+
+    #[test]
+    fn times_parse_as_unix_seconds() {
+        assert_eq!(parse_time("1714564800.5"), Some(1714564800.5));
+        assert_eq!(parse_time("2024-05-01T12:00:00Z"), Some(1714564800.0));
+        assert_eq!(
+            parse_time("2024-02-29T00:00:00.25-01:30"),
+            Some(1709170200.25)
+        );
+        assert_eq!(parse_time("1969-12-31 23:59:59Z"), Some(-1.0));
+        assert_eq!(parse_time("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_stat("p99.9"), Ok(Stat::Percentile(99.9)));
+        assert_eq!(parse_duration("1.5h"), Ok(5400.0));
+    }
+
+    #[test]
+    fn pushes_prometheus_buckets() {
+        let histo = Histogram::with_range(
+            &[1.0, 2.0, 2.0, 12.0],
+            2,
+            0.0,
+            10.0,
+            rhisto::OutOfRange::Drop,
+        )
+        .unwrap();
+        let text = prometheus_histogram("latency", &histo);
+        assert_eq!(
+            text,
+            "# TYPE latency histogram\nlatency_bucket{le=\"5\"} 3\nlatency_bucket{le=\"10\"} 3\n\
+             latency_bucket{le=\"+Inf\"} 4\nlatency_sum 5\nlatency_count 4\n"
+        );
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!(
+            "http://{}/metrics/job/rhisto",
+            listener.local_addr().unwrap()
+        );
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            write!(&stream, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            request
+        });
+        push(&url, &text).unwrap();
+        assert_eq!(
+            server.join().unwrap(),
+            "PUT /metrics/job/rhisto HTTP/1.1\r\n"
+        );
+    }
+
+    #[test]
+    fn record_seps_take_escapes() {
+        assert_eq!(parse_record_sep("\\0"), Ok(b'\0'));
+        assert_eq!(parse_record_sep(";"), Ok(b';'));
+        assert!(parse_record_sep("\\x").is_err());
+    }
+
+    #[test]
+    fn assertions_bound_statistics() {
+        let values = [1.0, 2.0, 3.0, 4.0];
+        let check = |s| parse_assertion(s).unwrap().check(&values);
+        assert_eq!(check("count >= 4"), None);
+        assert_eq!(check(" p50<3 "), None);
+        assert_eq!(
+            check("max < 4"),
+            Some("assertion `max < 4` failed: max is 4".to_string())
+        );
+        assert!(parse_assertion("p99 250").is_err());
+        assert!(parse_assertion("p99 < fast").is_err());
+    }
+
+    #[test]
+    fn optional_values_leave_the_input() {
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--kde", "data.csv"]);
+        assert_eq!(args.histogram.kde, Some(100));
+        assert_eq!(args.input.input, Some(PathBuf::from("data.csv")));
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--kde=20", "data.csv"]);
+        assert_eq!(args.histogram.kde, Some(20));
+
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--tdigest", "data.csv"]);
+        assert_eq!(args.histogram.tdigest, Some(100.0));
+        assert_eq!(args.input.input, Some(PathBuf::from("data.csv")));
+        assert!(Cli::try_parse_from(["rhisto", "-c", "0", "--tdigest=1e12"]).is_err());
+
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--hdr", "data.csv"]);
+        assert_eq!(args.histogram.hdr, Some(2));
+        assert_eq!(args.input.input, Some(PathBuf::from("data.csv")));
+
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--normalize", "data.csv"]);
+        assert_eq!(args.histogram.normalize, Some(Normalize::All));
+        assert_eq!(args.input.input, Some(PathBuf::from("data.csv")));
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--normalize=within-group"]);
+        assert_eq!(args.histogram.normalize, Some(Normalize::WithinGroup));
+
+        let args = Cli::parse_from(["rhisto", "compare", "-c", "0", "--qq", "a.csv", "b.csv"]);
+        let Some(Command::Compare {
+            input, other, qq, ..
+        }) = args.command
+        else {
+            panic!("expected compare");
+        };
+        assert_eq!(qq, Some(100));
+        assert_eq!(input.input, Some(PathBuf::from("a.csv")));
+        assert_eq!(other, Some(PathBuf::from("b.csv")));
+    }
+
+    #[test]
+    fn weights_count_in_assertions() {
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--weight-column", "1"]);
+        assert_eq!(args.histogram.weight_column, Some(1));
+        assert_eq!(parse_weight("0.25"), Ok(0.25));
+        assert!(matches!(
+            parse_weight("-1"),
+            Err(RowError::Failed("bad_weight", _))
+        ));
+
+        let values = [(1.0, 1.0), (4.0, 2.0), (10.0, 1.0)];
+        let check = |s| parse_assertion(s).unwrap().check_weighted(&values);
+        assert_eq!(check("count == 4"), None);
+        assert_eq!(check("mean == 4.75"), None);
+        assert_eq!(
+            check("p50 < 2"),
+            Some("assertion `p50 < 2` failed: p50 is 4".to_string())
+        );
+    }
+
+    #[test]
+    fn extract_prints_rows_meeting_the_condition() {
+        let rows = ["a,1,2", "b,3,-1", "c,5,4", "d,x,0"].map(str::to_string);
+        let condition = Condition::new("?2 > 0 && ?1 != 1", ",");
+        let fields = Fields {
+            columns: &[0, 1],
+            exprs: &["?1 * ?2".to_string()],
+            condition: Some(&condition),
+        };
+        let mut out = Vec::new();
+        extract(&mut out, rows.into_iter(), fields, ",", false).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "c,5,20\n");
+    }
+
+    #[test]
+    fn samples_follow_the_counts() {
+        let histo = Histogram::with_edges(&[0.5, 2.5, 2.5, 2.5], &[0.0, 1.0, 2.0, 3.0]).unwrap();
+        let draw = |seed| {
+            let mut out = Vec::new();
+            sample(&mut out, &histo, 400, seed).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+        let values: Vec<f64> = draw(7).lines().map(|line| line.parse().unwrap()).collect();
+
+        assert_eq!(values.len(), 400);
+        assert!(values.iter().all(|value| !(1.0..2.0).contains(value)));
+        let low = values.iter().filter(|&&value| value < 1.0).count();
+        assert!((60..140).contains(&low), "{} of 400 in the first bin", low);
+        assert_eq!(draw(7), draw(7));
+        assert_ne!(draw(7), draw(8));
+    }
+
+    #[test]
+    fn overlays_spread_the_total() {
+        let values = [0.5, 1.5, 2.5, 2.5, 3.0, 3.0, 3.5, 3.5];
+        let histo = Histogram::with_edges(&values, &[0.0, 1.0, 2.0, 4.0]).unwrap();
+        let stats = [
+            ("count", 8.0),
+            ("mean", 2.0),
+            ("stddev", 0.0),
+            ("min", 0.0),
+            ("max", 4.0),
+        ];
+        assert_eq!(
+            overlay_counts(&Overlay::Uniform, &histo, stats),
+            vec![2.0, 2.0, 4.0]
+        );
+        assert_eq!(
+            overlay_counts(&Overlay::Normal, &histo, stats),
+            vec![0.0, 0.0, 8.0]
+        );
+    }
+
+    #[test]
+    fn chart_marks_take_quantiles() {
+        let args = Cli::parse_from(["rhisto", "-c", "0", "--mark-quantiles", "0.5,0.99"]);
+        assert_eq!(args.histogram.mark_quantiles, vec![0.5, 0.99]);
+        assert!(parse_quantile("95").is_err());
+        assert!(parse_quantile("-0.1").is_err());
+    }
+
+    #[test]
+    fn range_caches_key_on_the_input_and_its_options() {
+        let dir = std::env::temp_dir().join(format!("rhisto-cache-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (data, path) = (dir.join("data.csv"), dir.join("ranges"));
+        std::fs::write(&data, "1\n5\n").unwrap();
+        let data = data.to_str().unwrap();
+        let input = |column| Cli::parse_from(["rhisto", "-c", column, data]).input;
+
+        let cache = RangeCache::new(&path, &input("0"));
+        assert_eq!(cache.get(), None);
+        cache.store(Some((1.0, 5.0)), 2);
+        assert_eq!(cache.get(), Some(((1.0, 5.0), 2)));
+        assert_eq!(RangeCache::new(&path, &input("1")).get(), None);
+        cache.store(Some((0.5, 5.0)), 3);
+        assert_eq!(cache.get(), Some(((0.5, 5.0), 3)));
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn external_sorts_match_in_memory_quantiles() {
+        let dir = std::env::temp_dir().join(format!("rhisto-sort-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let values: Vec<f64> = (0..100).map(|i| ((i * 37) % 100) as f64).collect();
+        let quantiles = [0.0, 0.25, 0.5, 0.99, 1.0];
+
+        let mut sort = ExternalSort::new(&dir, 7);
+        for &value in values.iter().chain(&[f64::NAN]) {
+            sort.push(value).unwrap();
+        }
+        let expected: Vec<f64> = quantiles
+            .iter()
+            .map(|&q| analysis::quantile(&values, q).unwrap())
+            .collect();
+        assert_eq!(sort.quantiles(&quantiles).unwrap(), expected);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+        assert!(ExternalSort::new(&dir, 7).quantiles(&[0.5]).unwrap()[0].is_nan());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sizes_count_in_1024s() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert_eq!(parse_size("1.5k"), Ok(1536));
+        assert!(parse_size("2X").is_err());
+        assert!(parse_size("-1M").is_err());
+    }
+
+    #[test]
+    fn fast_exprs_match_meval() {
+        let mut ctx = Context::new();
+        ctx.var("_0", 3.0).var("_1", 4.0);
+        let exprs: [(&str, &[f64]); 5] = [
+            ("?1", &[4.0]),
+            ("?0 - ?1", &[3.0, 4.0]),
+            ("1e3 * ?0", &[3.0]),
+            ("?1/2.5", &[4.0]),
+            ("?0 + 1", &[3.0]),
+        ];
+        for (expr, columns) in exprs {
+            assert_eq!(
+                FastExpr::parse(expr).unwrap().eval(columns),
+                meval::eval_str_with_context(expr.replace('?', "_"), &ctx).unwrap(),
+                "{}",
+                expr
+            );
+        }
+
+        assert_eq!(FastExpr::parse("?0 * ?1 + 1"), None);
+        assert_eq!(FastExpr::parse("sqrt(?0)"), None);
+    }
 }
@@ -0,0 +1,118 @@
+//! Binning rows of any format, by plugging in how a value is extracted from each.
+//!
+//! An [`Extractor`] turns a row into its value, or skips it, and a [`Pipeline`] runs one over
+//! rows to reuse the crate's binning, statistics and rendering, e.g. for protobuf or custom
+//! binary records the CLI cannot read. Closures from a row to its value are extractors, as
+//! are single column [`ColumnParser`]s.
+
+use crate::{Binning, ColumnParser, Error, Histogram, Stats};
+
+/// Gets the value of a row, or `None` to leave the row out.
+pub trait Extractor {
+    fn extract(&self, row: &str) -> Result<Option<f64>, Error>;
+}
+
+impl<F: Fn(&str) -> Result<Option<f64>, Error>> Extractor for F {
+    fn extract(&self, row: &str) -> Result<Option<f64>, Error> {
+        self(row)
+    }
+}
+
+/// Extracts the first of the parser's columns.
+impl Extractor for ColumnParser<f64> {
+    fn extract(&self, row: &str) -> Result<Option<f64>, Error> {
+        Ok(self.parse_row(row)?.first().copied())
+    }
+}
+
+/// Runs an [`Extractor`] over rows, failing with [`Error::Row`] at the first row it fails on.
+pub struct Pipeline<E> {
+    extractor: E,
+}
+
+impl<E: Extractor> Pipeline<E> {
+    pub fn new(extractor: E) -> Self {
+        Self { extractor }
+    }
+
+    /// The values extracted from `rows`, leaving out skipped rows.
+    pub fn values<S: AsRef<str>>(
+        &self,
+        rows: impl IntoIterator<Item = S>,
+    ) -> Result<Vec<f64>, Error> {
+        let mut values = Vec::new();
+        for (i, row) in rows.into_iter().enumerate() {
+            match self.extractor.extract(row.as_ref()) {
+                Ok(Some(value)) => values.push(value),
+                Ok(None) => {}
+                Err(err) => return Err(Error::Row(i, Box::new(err))),
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// The values extracted from `rows`, counted into the bins `binning` picks for them.
+    pub fn histogram<S: AsRef<str>>(
+        &self,
+        rows: impl IntoIterator<Item = S>,
+        binning: &impl Binning,
+    ) -> Result<Histogram, Error> {
+        Histogram::binned(&self.values(rows)?, binning)
+    }
+
+    /// The statistics of the values extracted from `rows`, or `None` if there are none.
+    pub fn stats<S: AsRef<str>>(
+        &self,
+        rows: impl IntoIterator<Item = S>,
+    ) -> Result<Option<Stats>, Error> {
+        Ok(Stats::from_values(&self.values(rows)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Exponential;
+
+    /// Little endian `u32` microseconds, hex encoded one record per row.
+    struct Micros;
+
+    impl Extractor for Micros {
+        fn extract(&self, row: &str) -> Result<Option<f64>, Error> {
+            let bad = || Error::FailedParse(row.to_string(), "hex u32".to_string());
+            let bytes: Vec<u8> = (0..row.len())
+                .step_by(2)
+                .map(|i| {
+                    row.get(i..i + 2)
+                        .and_then(|b| u8::from_str_radix(b, 16).ok())
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(bad)?;
+            let bytes: [u8; 4] = bytes.try_into().map_err(|_| bad())?;
+            Ok(Some(u32::from_le_bytes(bytes) as f64 / 1000.0))
+        }
+    }
+
+    #[test]
+    fn custom_extractors_reuse_binning() {
+        let pipeline = Pipeline::new(Micros);
+        let rows = ["e8030000", "d0070000", "b80b0000"];
+        assert_eq!(pipeline.values(rows), Ok(vec![1.0, 2.0, 3.0]));
+        assert_eq!(pipeline.stats(rows).unwrap().unwrap().mean, 2.0);
+
+        let histo = pipeline.histogram(rows, &Exponential::default()).unwrap();
+        assert_eq!(histo.iter().map(|bin| bin.count).sum::<f64>(), 3.0);
+
+        assert!(matches!(
+            pipeline.values(["e8030000", "zz"]),
+            Err(Error::Row(1, _))
+        ));
+    }
+
+    #[test]
+    fn column_parsers_are_extractors() {
+        let pipeline = Pipeline::new(ColumnParser::<f64>::single(1, ","));
+        assert_eq!(pipeline.values(["a,1", "b,2.5"]), Ok(vec![1.0, 2.5]));
+    }
+}
@@ -0,0 +1,304 @@
+//! The `.rhst` saved histogram format.
+//!
+//! An `.rhst` file is line oriented text headed by a `rhst <version>` line. Each following
+//! line starts with a keyword:
+//!
+//! ```text
+//! rhst 1
+//! meta input latency.csv
+//! stat mean 12.5
+//! edges 0 5 10 15 20
+//! counts 3 9 4 1
+//! summary 17 9.7 4.1 0.2 19.5
+//! flow 2 0
+//! group host-a
+//! edges 0 5 10 15 20
+//! counts 1 5 2 0
+//! ```
+//!
+//! `edges`, `counts`, the optional `summary` of the values' count, mean, standard deviation,
+//! minimum and maximum, and the optional `flow` of values below and above the edges before the
+//! first `group` line are the histogram itself, those after a `group` line belong to that
+//! group. Edges strictly ascend, so bins are always written and read lowest first. Readers skip
+//! keywords they do not know, so new keywords can be added without a version bump; the version
+//! only changes when existing lines change meaning.
+
+use crate::{Error, Histogram, Stats, valid_edges};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// The newest `.rhst` version this crate reads and the version it writes.
+pub const VERSION: u32 = 1;
+
+/// A histogram along with the statistics, metadata and groups saved with it.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rhst {
+    pub histogram: Histogram,
+    pub stats: BTreeMap<String, f64>,
+    pub metadata: BTreeMap<String, String>,
+    pub groups: Vec<(String, Histogram)>,
+}
+
+impl Rhst {
+    pub fn new(histogram: Histogram) -> Self {
+        Self {
+            histogram,
+            stats: BTreeMap::new(),
+            metadata: BTreeMap::new(),
+            groups: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for Rhst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "rhst {}", VERSION)?;
+        for (key, value) in &self.metadata {
+            writeln!(f, "meta {} {}", key, escape(value))?;
+        }
+        for (name, value) in &self.stats {
+            writeln!(f, "stat {} {}", name, value)?;
+        }
+
+        write_histogram(f, &self.histogram)?;
+        for (name, histogram) in &self.groups {
+            writeln!(f, "group {}", escape(name))?;
+            write_histogram(f, histogram)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Rhst {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines().enumerate().map(|(i, line)| (i + 1, line));
+        let version = lines
+            .next()
+            .and_then(|(_, line)| line.strip_prefix("rhst "))
+            .ok_or_else(|| Error::MalformedRhst(1, "missing `rhst` header".to_string()))?;
+        let version = version
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| Error::MalformedRhst(1, format!("bad version `{}`", version)))?;
+        if version > VERSION {
+            return Err(Error::UnsupportedRhstVersion(version));
+        }
+
+        let mut stats = BTreeMap::new();
+        let mut metadata = BTreeMap::new();
+        let mut sections = vec![Section::new(None, 1)];
+        for (n, line) in lines {
+            let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let malformed = |reason: &str| Error::MalformedRhst(n, reason.to_string());
+            let section = sections.last_mut().expect("always at least one section");
+            match keyword {
+                "meta" => {
+                    let (key, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                    metadata.insert(key.to_string(), unescape(value));
+                }
+                "stat" => {
+                    let (name, value) = rest
+                        .split_once(' ')
+                        .ok_or_else(|| malformed("stat without a value"))?;
+                    let value = value.parse().map_err(|_| malformed("bad stat value"))?;
+                    stats.insert(name.to_string(), value);
+                }
+                "edges" => section.edges = parse_list(rest).map_err(|_| malformed("bad edge"))?,
+                "counts" => {
+                    section.counts = parse_list(rest).map_err(|_| malformed("bad count"))?
+                }
+                "summary" => {
+                    let values: Vec<f64> =
+                        parse_list(rest).map_err(|_| malformed("bad summary"))?;
+                    let [count, mean, stddev, min, max] = values[..] else {
+                        return Err(malformed("summary needs five values"));
+                    };
+                    section.stats = Some(Stats {
+                        count,
+                        mean,
+                        stddev,
+                        min,
+                        max,
+                    });
+                }
+                "flow" => {
+                    let values: Vec<f64> = parse_list(rest).map_err(|_| malformed("bad flow"))?;
+                    let [underflow, overflow] = values[..] else {
+                        return Err(malformed("flow needs two values"));
+                    };
+                    section.flow = (underflow, overflow);
+                }
+                "group" => sections.push(Section::new(Some(unescape(rest)), n)),
+                _ => {}
+            }
+        }
+
+        let mut histograms = sections.into_iter().map(|section| {
+            let (edges, counts) = (section.edges, section.counts);
+            if edges.len() != counts.len() + 1 && !(edges.is_empty() && counts.is_empty()) {
+                return Err(Error::MalformedRhst(
+                    section.line,
+                    format!("{} edges do not bound {} counts", edges.len(), counts.len()),
+                ));
+            }
+            if !edges.is_empty() && !valid_edges(&edges) {
+                return Err(Error::MalformedRhst(
+                    section.line,
+                    "edges do not ascend".to_string(),
+                ));
+            }
+
+            let mut histogram = Histogram::from_parts(edges, counts);
+            histogram.stats = section.stats;
+            (histogram.underflow, histogram.overflow) = section.flow;
+            Ok((section.name, histogram))
+        });
+
+        let (_, histogram) = histograms.next().expect("always at least one section")?;
+        let groups = histograms
+            .map(|section| section.map(|(name, histogram)| (name.unwrap_or_default(), histogram)))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            histogram,
+            stats,
+            metadata,
+            groups,
+        })
+    }
+}
+
+/// The edges and counts of one histogram in a file, named unless it is the top level one.
+struct Section {
+    name: Option<String>,
+    line: usize,
+    edges: Vec<f64>,
+    counts: Vec<f64>,
+    stats: Option<Stats>,
+    flow: (f64, f64),
+}
+
+impl Section {
+    fn new(name: Option<String>, line: usize) -> Self {
+        Self {
+            name,
+            line,
+            edges: Vec::new(),
+            counts: Vec::new(),
+            stats: None,
+            flow: (0.0, 0.0),
+        }
+    }
+}
+
+fn write_histogram(f: &mut fmt::Formatter<'_>, histogram: &Histogram) -> fmt::Result {
+    let edges: Vec<String> = histogram.edges.iter().map(f64::to_string).collect();
+    let counts: Vec<String> = histogram
+        .bins
+        .iter()
+        .map(|bin| bin.count.to_string())
+        .collect();
+    writeln!(f, "edges {}", edges.join(" "))?;
+    writeln!(f, "counts {}", counts.join(" "))?;
+    if let Some(stats) = histogram.stats() {
+        writeln!(
+            f,
+            "summary {} {} {} {} {}",
+            stats.count, stats.mean, stats.stddev, stats.min, stats.max
+        )?;
+    }
+    if histogram.underflow() != 0.0 || histogram.overflow() != 0.0 {
+        writeln!(f, "flow {} {}", histogram.underflow(), histogram.overflow())?;
+    }
+
+    Ok(())
+}
+
+fn parse_list<T: FromStr>(s: &str) -> Result<Vec<T>, T::Err> {
+    s.split_whitespace().map(str::parse).collect()
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let mut rhst = Rhst::new(Histogram::with_edges(&values, &[0.5, 1.5, 2.5]).unwrap());
+        rhst.stats.insert("mean".to_string(), 1.6);
+        rhst.metadata
+            .insert("input".to_string(), "two\nlines \\ here".to_string());
+        rhst.groups.push((
+            "host a".to_string(),
+            Histogram::from_values(values, 2).unwrap(),
+        ));
+
+        assert_eq!(rhst.to_string().parse::<Rhst>(), Ok(rhst));
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_round_trip() {
+        let values = vec![2.0, 1.0, 2.0, 3.0, 3.0, 2.0, 0.0, 1.0, 1.0, 1.0];
+        let rhst = Rhst::new(Histogram::from_values(values, 3).unwrap());
+
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&rhst, &mut bytes).unwrap();
+        assert_eq!(ciborium::from_reader::<Rhst, _>(&bytes[..]).unwrap(), rhst);
+    }
+
+    #[test]
+    fn skips_unknown_keywords() {
+        let rhst: Rhst = "rhst 1\nedges 0 1 2\nsketch abc\ncounts 4 1\n"
+            .parse()
+            .unwrap();
+        assert_eq!(rhst.histogram.into_counts(), vec![4.0, 1.0]);
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        assert_eq!(
+            "rhst 2\n".parse::<Rhst>(),
+            Err(Error::UnsupportedRhstVersion(2))
+        );
+    }
+
+    #[test]
+    fn rejects_mismatched_edges() {
+        assert!(matches!(
+            "rhst 1\nedges 0 1\ncounts 4 1\n".parse::<Rhst>(),
+            Err(Error::MalformedRhst(..))
+        ));
+        assert!(matches!(
+            "rhst 1\nedges 0 2 1\ncounts 4 1\n".parse::<Rhst>(),
+            Err(Error::MalformedRhst(..))
+        ));
+    }
+}
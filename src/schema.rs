@@ -0,0 +1,262 @@
+//! Declared column types and ranges to check input rows against.
+//!
+//! A schema is written in a small subset of TOML, one `[[column]]` table per checked column:
+//!
+//! ```toml
+//! [[column]]
+//! index = 1
+//! name = "latency"
+//! type = "float"
+//! min = 0
+//! max = 30000
+//! ```
+//!
+//! `index` is the zero indexed column and is required. `type` is `integer`, `float` or
+//! `string`, defaulting to `string`, and `min` and `max` bound numeric columns inclusively.
+
+use crate::{ColumnParser, Error, sniff::ColumnType};
+use std::str::FromStr;
+
+/// The columns rows are checked against, in the order they were declared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub columns: Vec<ColumnSchema>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnSchema {
+    pub index: u32,
+    pub name: Option<String>,
+    pub kind: ColumnType,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+/// How a row breaks its schema.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    /// The row has too few columns to hold the column.
+    Missing,
+    /// The column's text does not parse as its type.
+    WrongType(String),
+    /// The column's value is outside its `min` and `max`.
+    OutOfRange(f64),
+}
+
+impl ColumnSchema {
+    /// The column's index, followed by its name if it has one.
+    pub fn describe(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({})", self.index, name),
+            None => self.index.to_string(),
+        }
+    }
+
+    fn check(&self, row: &str, delim: &str) -> Option<Violation> {
+        let value = match self.kind {
+            ColumnType::Integer => ColumnParser::<i64>::single(self.index, delim)
+                .parse_row(row)
+                .map(|values| values[0] as f64),
+            ColumnType::Float => ColumnParser::<f64>::single(self.index, delim)
+                .parse_row(row)
+                .map(|values| values[0]),
+            ColumnType::String => {
+                return ColumnParser::<String>::single(self.index, delim)
+                    .parse_row(row)
+                    .err()
+                    .map(|_| Violation::Missing);
+            }
+        };
+
+        match value {
+            Err(Error::FailedParse(text, _)) => Some(Violation::WrongType(text)),
+            Err(_) => Some(Violation::Missing),
+            Ok(value)
+                if self.min.is_some_and(|min| value < min)
+                    || self.max.is_some_and(|max| value > max) =>
+            {
+                Some(Violation::OutOfRange(value))
+            }
+            Ok(_) => None,
+        }
+    }
+}
+
+impl Schema {
+    /// Each declared column `row` breaks, along with how.
+    pub fn check(&self, row: &str, delim: &str) -> Vec<(&ColumnSchema, Violation)> {
+        self.columns
+            .iter()
+            .filter_map(|column| Some((column, column.check(row, delim)?)))
+            .collect()
+    }
+}
+
+impl FromStr for Schema {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tables: Vec<(usize, Keys)> = Vec::new();
+        for (n, line) in s.lines().enumerate().map(|(i, line)| (i + 1, line)) {
+            let malformed = |reason: &str| Error::MalformedSchema(n, reason.to_string());
+            let line = strip_comment(line).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line == "[[column]]" {
+                tables.push((n, Vec::new()));
+                continue;
+            }
+
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| malformed("expected `key = value` or `[[column]]`"))?;
+            let value = Value::parse(value.trim()).ok_or_else(|| malformed("bad value"))?;
+            let (_, keys) = tables
+                .last_mut()
+                .ok_or_else(|| malformed("key outside a `[[column]]` table"))?;
+            keys.push((n, key.trim().to_string(), value));
+        }
+
+        let columns = tables
+            .into_iter()
+            .map(|(n, keys)| column_schema(n, keys))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { columns })
+    }
+}
+
+fn column_schema(line: usize, keys: Keys) -> Result<ColumnSchema, Error> {
+    let mut index = None;
+    let mut column = ColumnSchema {
+        index: 0,
+        name: None,
+        kind: ColumnType::String,
+        min: None,
+        max: None,
+    };
+    for (n, key, value) in keys {
+        let malformed = |reason: &str| Error::MalformedSchema(n, reason.to_string());
+        match (key.as_str(), value) {
+            ("index", Value::Number(v))
+                if v.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&v) =>
+            {
+                index = Some(v as u32)
+            }
+            ("name", Value::String(name)) => column.name = Some(name),
+            ("type", Value::String(kind)) => {
+                column.kind = match kind.as_str() {
+                    "integer" => ColumnType::Integer,
+                    "float" => ColumnType::Float,
+                    "string" => ColumnType::String,
+                    _ => return Err(malformed("type must be integer, float or string")),
+                }
+            }
+            ("min", Value::Number(min)) => column.min = Some(min),
+            ("max", Value::Number(max)) => column.max = Some(max),
+            ("index" | "name" | "type" | "min" | "max", _) => {
+                return Err(malformed(&format!("bad `{}` value", key)));
+            }
+            _ => return Err(malformed(&format!("unknown key `{}`", key))),
+        }
+    }
+
+    column.index = index
+        .ok_or_else(|| Error::MalformedSchema(line, "column without an `index`".to_string()))?;
+    if column.kind == ColumnType::String && (column.min.is_some() || column.max.is_some()) {
+        return Err(Error::MalformedSchema(
+            line,
+            "string columns have no range".to_string(),
+        ));
+    }
+
+    Ok(column)
+}
+
+/// The keys of a `[[column]]` table, each with the line it is on.
+type Keys = Vec<(usize, String, Value)>;
+
+enum Value {
+    String(String),
+    Number(f64),
+}
+
+impl Value {
+    fn parse(s: &str) -> Option<Self> {
+        match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(s) => Some(Value::String(s.to_string())),
+            None => s.replace('_', "").parse().ok().map(Value::Number),
+        }
+    }
+}
+
+/// `line` up to any `#` outside a string.
+fn strip_comment(line: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            '#' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+
+    line
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        # Request logs.
+        [[column]]
+        index = 0
+        name = "host"
+
+        [[column]]
+        index = 1
+        type = "float"
+        min = 0
+        max = 30_000 # ms
+    "#;
+
+    #[test]
+    fn parses_columns() {
+        let schema: Schema = SCHEMA.parse().unwrap();
+        assert_eq!(
+            schema.columns[1],
+            ColumnSchema {
+                index: 1,
+                name: None,
+                kind: ColumnType::Float,
+                min: Some(0.0),
+                max: Some(30000.0),
+            }
+        );
+        assert!(matches!(
+            "[[column]]\nname = \"a\"\n".parse::<Schema>(),
+            Err(Error::MalformedSchema(1, _))
+        ));
+    }
+
+    #[test]
+    fn reports_violations() {
+        let schema: Schema = SCHEMA.parse().unwrap();
+        assert!(schema.check("a,12.5", ",").is_empty());
+
+        let violations: Vec<Violation> = ["a,-1", "a,fast", "a"]
+            .iter()
+            .flat_map(|row| schema.check(row, ","))
+            .map(|(_, violation)| violation)
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                Violation::OutOfRange(-1.0),
+                Violation::WrongType("fast".to_string()),
+                Violation::Missing
+            ]
+        );
+    }
+}
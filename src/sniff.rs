@@ -0,0 +1,156 @@
+//! Guessing the delimiter, quoting, header and column types of delimited text.
+
+/// The delimiters tried, in order of preference when several fit equally well.
+const DELIMITERS: [char; 5] = [',', '\t', ';', '|', ' '];
+
+/// The layout of a delimited text sample, as far as it can be told from the sample.
+#[derive(Debug, PartialEq)]
+pub struct Dialect {
+    pub delimiter: char,
+    /// Whether any field is wrapped in double quotes.
+    pub quoted: bool,
+    /// Whether the first row names the columns rather than holding data.
+    pub header: bool,
+    pub columns: Vec<ColumnType>,
+}
+
+/// The narrowest type every data value in a column parses as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    String,
+}
+
+impl ColumnType {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::Float => "float",
+            ColumnType::String => "string",
+        }
+    }
+
+    fn of(field: &str) -> Self {
+        let field = field.trim();
+        if field.parse::<i64>().is_ok() {
+            ColumnType::Integer
+        } else if field.parse::<f64>().is_ok() {
+            ColumnType::Float
+        } else {
+            ColumnType::String
+        }
+    }
+
+    fn widen(self, other: Self) -> Self {
+        match (self, other) {
+            (ColumnType::String, _) | (_, ColumnType::String) => ColumnType::String,
+            (ColumnType::Float, _) | (_, ColumnType::Float) => ColumnType::Float,
+            _ => ColumnType::Integer,
+        }
+    }
+}
+
+/// Guesses the dialect of the non-empty lines of `sample`, or `None` if it has none.
+///
+/// The delimiter is the one splitting the most lines into the same number of fields, falling
+/// back to a single column when none splits any line. A header is assumed when the first row
+/// has text in a column whose other values are all numbers.
+pub fn sniff(sample: &str) -> Option<Dialect> {
+    let lines: Vec<&str> = sample.lines().filter(|line| !line.is_empty()).collect();
+    if lines.is_empty() {
+        return None;
+    }
+
+    let delimiter = DELIMITERS
+        .iter()
+        .filter_map(|&delimiter| {
+            let widths: Vec<usize> = lines
+                .iter()
+                .map(|line| line.split(delimiter).count())
+                .collect();
+            let width = widths[0];
+            let consistent = widths.iter().filter(|&&w| w == width).count();
+            (width > 1).then_some((delimiter, consistent))
+        })
+        // `max_by_key` keeps the last maximum, so reverse to prefer earlier delimiters.
+        .rev()
+        .max_by_key(|&(_, consistent)| consistent)
+        .map_or(DELIMITERS[0], |(delimiter, _)| delimiter);
+
+    let rows: Vec<Vec<&str>> = lines
+        .iter()
+        .map(|line| line.split(delimiter).map(unquote).collect())
+        .collect();
+    let quoted = lines
+        .iter()
+        .flat_map(|line| line.split(delimiter))
+        .any(|field| unquote(field) != field);
+
+    let types = |rows: &[Vec<&str>]| {
+        let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        (0..width)
+            .map(|i| {
+                rows.iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|field| ColumnType::of(field))
+                    .reduce(ColumnType::widen)
+                    .unwrap_or(ColumnType::String)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let header = rows.len() > 1
+        && types(&rows[1..])
+            .iter()
+            .zip(&rows[0])
+            .any(|(&column, first)| {
+                column != ColumnType::String && ColumnType::of(first) == ColumnType::String
+            });
+    let columns = types(if header { &rows[1..] } else { &rows[..] });
+
+    Some(Dialect {
+        delimiter,
+        quoted,
+        header,
+        columns,
+    })
+}
+
+fn unquote(field: &str) -> &str {
+    field
+        .strip_prefix('"')
+        .and_then(|field| field.strip_suffix('"'))
+        .unwrap_or(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffs_headed_csv() {
+        let dialect = sniff("host,latency,status\n\"a\",1.5,200\n\"b\",2,404\n").unwrap();
+        assert_eq!(
+            dialect,
+            Dialect {
+                delimiter: ',',
+                quoted: true,
+                header: true,
+                columns: vec![ColumnType::String, ColumnType::Float, ColumnType::Integer],
+            }
+        );
+    }
+
+    #[test]
+    fn sniffs_headerless_tabs() {
+        let dialect = sniff("1\t2.5\n3\t4\n").unwrap();
+        assert_eq!(dialect.delimiter, '\t');
+        assert!(!dialect.header);
+        assert_eq!(
+            dialect.columns,
+            vec![ColumnType::Integer, ColumnType::Float]
+        );
+        assert_eq!(sniff("\n"), None);
+    }
+}
@@ -0,0 +1,303 @@
+//! A t-digest, a sketch of a stream of values that answers quantile queries in bounded memory
+//! without knowing the values' range ahead of time.
+//!
+//! Values are summarized by centroids, a mean and a weight each, which are kept small near the
+//! extremes and allowed to grow in the middle, so tail quantiles stay accurate. The number of
+//! centroids is bounded by about the `compression` however many values are pushed.
+
+use crate::{Error, Histogram, HistogramBuilder, Sample};
+use std::{borrow::Cow, f64::consts::PI};
+
+/// How many times the compression's worth of values are buffered before they are merged into
+/// the centroids.
+const BUFFER_FACTOR: usize = 5;
+
+/// The largest compression a digest takes, as its buffers are sized from the compression and
+/// more centroids than this add memory without adding useful accuracy.
+pub const MAX_COMPRESSION: f64 = 1e5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Centroid {
+    pub mean: f64,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TDigest {
+    compression: f64,
+    /// Merged centroids, in ascending order of mean.
+    centroids: Vec<Centroid>,
+    /// Values pushed since the last merge.
+    buffer: Vec<f64>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Default for TDigest {
+    fn default() -> Self {
+        Self::new(100.0).expect("100 is a valid compression")
+    }
+}
+
+impl TDigest {
+    /// An empty digest keeping around `compression` centroids. Higher compressions are more
+    /// accurate and take more memory; 100 suits most uses. Errors unless `compression` is
+    /// positive and at most [`MAX_COMPRESSION`].
+    pub fn new(compression: f64) -> Result<Self, Error> {
+        if !(compression > 0.0 && compression <= MAX_COMPRESSION) {
+            return Err(Error::InvalidCompression(compression));
+        }
+
+        Ok(Self {
+            compression,
+            centroids: Vec::new(),
+            buffer: Vec::new(),
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        })
+    }
+
+    /// Adds `value` to the digest, unless it is NaN or infinite.
+    pub fn push<T: Sample>(&mut self, value: T) {
+        let value = value.to_f64();
+        if !value.is_finite() {
+            return;
+        }
+
+        self.buffer.push(value);
+        self.count += 1.0;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if self.buffer.len() >= BUFFER_FACTOR * self.compression.ceil() as usize {
+            self.centroids = self.merged().into_owned();
+            self.buffer.clear();
+        }
+    }
+
+    /// Adds the values summarized by `other`, as if they had been pushed to `self`.
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.count == 0.0 {
+            return;
+        }
+
+        let mut centroids = std::mem::take(&mut self.centroids);
+        centroids.extend(other.merged().iter());
+        centroids.extend(
+            self.buffer
+                .drain(..)
+                .map(|mean| Centroid { mean, weight: 1.0 }),
+        );
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.centroids = compress(centroids, self.count, self.compression);
+    }
+
+    /// The number of values pushed.
+    pub fn count(&self) -> f64 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0.0).then_some(self.min)
+    }
+
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0.0).then_some(self.max)
+    }
+
+    /// The centroids summarizing every value pushed, in ascending order of mean.
+    pub fn centroids(&self) -> Cow<'_, [Centroid]> {
+        self.merged()
+    }
+
+    /// The estimated `p` quantile, from 0 to 1, interpolating between centroids and out to the
+    /// exact minimum and maximum. `None` before any value is pushed.
+    pub fn quantile(&self, p: f64) -> Option<f64> {
+        let centroids = self.merged();
+        let (first, last) = (centroids.first()?, centroids.last()?);
+        let target = p.clamp(0.0, 1.0) * self.count;
+
+        // Each centroid's mean sits at the middle of the weight it covers.
+        if target <= first.weight / 2.0 {
+            return Some(lerp(self.min, first.mean, target / (first.weight / 2.0)));
+        }
+        if target >= self.count - last.weight / 2.0 {
+            let from = self.count - last.weight / 2.0;
+            return Some(lerp(
+                last.mean,
+                self.max,
+                (target - from) / (last.weight / 2.0),
+            ));
+        }
+
+        let mut below = 0.0;
+        for pair in centroids.windows(2) {
+            let (left, right) = (
+                below + pair[0].weight / 2.0,
+                below + pair[0].weight + pair[1].weight / 2.0,
+            );
+            if target <= right {
+                return Some(lerp(
+                    pair[0].mean,
+                    pair[1].mean,
+                    (target - left) / (right - left),
+                ));
+            }
+            below += pair[0].weight;
+        }
+
+        Some(self.max)
+    }
+
+    /// An approximate histogram of the values pushed, each centroid's weight counted at its
+    /// mean in one of `num_bins` even bins spanning the minimum to the maximum. Fails as
+    /// [`Histogram::from_values`] does when the values leave the bins no width.
+    pub fn histogram(&self, num_bins: usize) -> Result<Histogram, Error> {
+        match (self.min(), self.max()) {
+            _ if num_bins == 0 => Err(Error::NoBins),
+            (Some(min), Some(max)) if min == max && self.count == 1.0 => {
+                Err(Error::SingleValue(min))
+            }
+            (Some(min), Some(max)) if min == max => Err(Error::IdenticalValues(min)),
+            (Some(min), Some(max)) => {
+                let mut builder = HistogramBuilder::new(min, max, num_bins)?;
+                for centroid in self.merged().iter() {
+                    builder.push_weighted(centroid.mean, centroid.weight);
+                }
+                Ok(builder.finish())
+            }
+            _ => Err(Error::NoValues),
+        }
+    }
+
+    /// The centroids with any buffered values merged in.
+    fn merged(&self) -> Cow<'_, [Centroid]> {
+        if self.buffer.is_empty() {
+            return Cow::Borrowed(&self.centroids);
+        }
+
+        let mut centroids = self.centroids.clone();
+        centroids.extend(
+            self.buffer
+                .iter()
+                .map(|&mean| Centroid { mean, weight: 1.0 }),
+        );
+        Cow::Owned(compress(centroids, self.count, self.compression))
+    }
+}
+
+/// Merges neighboring `centroids`, of `count` weight in all, while each stays within one unit
+/// of the arcsine scale function, which keeps centroids near the extremes small.
+fn compress(mut centroids: Vec<Centroid>, count: f64, compression: f64) -> Vec<Centroid> {
+    centroids.sort_by(|a, b| a.mean.total_cmp(&b.mean));
+    let scale = |q: f64| compression / (2.0 * PI) * (2.0 * q - 1.0).asin();
+    let unscale = |k: f64| ((k * 2.0 * PI / compression).sin() + 1.0) / 2.0;
+
+    let mut merged: Vec<Centroid> = Vec::with_capacity(compression.ceil() as usize);
+    let mut centroids = centroids.into_iter();
+    let Some(mut current) = centroids.next() else {
+        return merged;
+    };
+    let mut below = 0.0;
+    let mut limit = unscale(scale(0.0) + 1.0);
+    for centroid in centroids {
+        if (below + current.weight + centroid.weight) / count <= limit {
+            let weight = current.weight + centroid.weight;
+            current.mean += (centroid.mean - current.mean) * centroid.weight / weight;
+            current.weight = weight;
+        } else {
+            below += current.weight;
+            merged.push(current);
+            limit = unscale(scale(below / count) + 1.0);
+            current = centroid;
+        }
+    }
+    merged.push(current);
+
+    merged
+}
+
+fn lerp(from: f64, to: f64, t: f64) -> f64 {
+    from + (to - from) * t.clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantiles_of_a_stream() {
+        let mut digest = TDigest::new(100.0).unwrap();
+        // A scrambled 0 to 9999, so centroids are merged from out of order values.
+        for i in 0..10_000u64 {
+            digest.push((i * 7919 % 10_000) as f64);
+        }
+
+        assert!(digest.centroids().len() <= 200);
+        assert_eq!(digest.quantile(0.0), Some(0.0));
+        assert_eq!(digest.quantile(1.0), Some(9999.0));
+        for (p, expected) in [(0.5, 5000.0), (0.99, 9900.0), (0.001, 10.0)] {
+            let estimate = digest.quantile(p).unwrap();
+            assert!(
+                (estimate - expected).abs() < 25.0,
+                "p{} is {}",
+                p * 100.0,
+                estimate
+            );
+        }
+        assert_eq!(TDigest::default().quantile(0.5), None);
+    }
+
+    #[test]
+    fn merged_digests_match_one_stream() {
+        let (mut a, mut b, mut both) = (
+            TDigest::new(50.0).unwrap(),
+            TDigest::new(50.0).unwrap(),
+            TDigest::new(50.0).unwrap(),
+        );
+        for i in 0..1000 {
+            let value = i as f64;
+            if i % 3 == 0 {
+                a.push(value)
+            } else {
+                b.push(value)
+            }
+            both.push(value);
+        }
+        a.merge(&b);
+
+        assert_eq!(a.count(), 1000.0);
+        assert_eq!((a.min(), a.max()), (Some(0.0), Some(999.0)));
+        let median = a.quantile(0.5).unwrap();
+        assert!((median - both.quantile(0.5).unwrap()).abs() < 10.0);
+    }
+
+    #[test]
+    fn histograms_keep_every_value() {
+        let mut digest = TDigest::new(20.0).unwrap();
+        for value in [1.0, 2.0, 2.0, 3.0, 9.0, f64::NAN] {
+            digest.push(value);
+        }
+
+        let histo = digest.histogram(4).unwrap();
+        assert_eq!(histo.edges(), &[1.0, 3.0, 5.0, 7.0, 9.0]);
+        assert_eq!(histo.into_counts().iter().sum::<f64>(), 5.0);
+
+        let mut digest = TDigest::new(20.0).unwrap();
+        digest.push(4.0);
+        assert_eq!(digest.histogram(4), Err(Error::SingleValue(4.0)));
+    }
+
+    #[test]
+    fn compressions_are_bounded() {
+        assert_eq!(TDigest::new(0.0), Err(Error::InvalidCompression(0.0)));
+        assert_eq!(TDigest::new(1e12), Err(Error::InvalidCompression(1e12)));
+        assert!(TDigest::new(f64::NAN).is_err());
+        assert!(TDigest::new(MAX_COMPRESSION).is_ok());
+    }
+}
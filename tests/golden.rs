@@ -0,0 +1,127 @@
+//! Runs the CLI over `tests/data` and compares each output format with its file under
+//! `tests/golden`, so any change to what downstream tools diff shows up here first.
+//!
+//! After an intended change, rerun with `UPDATE_GOLDEN=1` to rewrite the golden files.
+
+use std::{path::PathBuf, process::Command};
+
+fn root() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests")
+}
+
+/// Checks `rhisto` run with `args` on `tests/data/latency.csv` against `tests/golden/<name>`.
+fn check(name: &str, args: &[&str]) {
+    let input = data("latency.csv");
+    check_run(name, &[args, &[&input]].concat());
+}
+
+/// The path of `file` under `tests/data`.
+fn data(file: &str) -> String {
+    root().join("data").join(file).display().to_string()
+}
+
+/// Checks `rhisto` run with exactly `args` against `tests/golden/<name>`.
+fn check_run(name: &str, args: &[&str]) {
+    let output = Command::new(env!("CARGO_BIN_EXE_rhisto"))
+        .args(args)
+        // Output must not depend on the locale, so run under one with a decimal comma.
+        .env("LC_ALL", "de_DE.UTF-8")
+        .output()
+        .expect("failed to run rhisto");
+    assert!(
+        output.status.success(),
+        "rhisto {} failed: {}",
+        args.join(" "),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let actual = String::from_utf8(output.stdout).expect("output is UTF-8");
+    let path = root().join("golden").join(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, &actual).expect("failed to write golden file");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(&path).expect("failed to read golden file");
+    assert_eq!(actual, expected, "output differs from {}", path.display());
+}
+
+const HISTOGRAM: [&str; 6] = ["-c", "1", "-s", "-n", "5", "--no-metadata"];
+
+#[test]
+fn text() {
+    check("text.txt", &HISTOGRAM);
+}
+
+#[test]
+fn literals() {
+    for format in ["rust", "python", "numpy"] {
+        check(
+            &format!("{}.txt", format),
+            &[&HISTOGRAM[..], &["--format-out", format]].concat(),
+        );
+    }
+}
+
+#[test]
+fn rhst() {
+    check(
+        "histogram.rhst",
+        &[&HISTOGRAM[..], &["--format-out", "rhst"]].concat(),
+    );
+}
+
+#[test]
+fn json() {
+    check(
+        "histogram.json",
+        &[&HISTOGRAM[..], &["--format-out", "json"]].concat(),
+    );
+}
+
+#[test]
+fn categorical() {
+    check("categorical.txt", &["-c", "0", "-s", "--categorical"]);
+}
+
+#[test]
+fn bin_width() {
+    check(
+        "bin_width.txt",
+        &["-c", "1", "-s", "--bin-width", "10", "--no-metadata"],
+    );
+}
+
+#[test]
+fn kde() {
+    check("kde.txt", &["-c", "1", "-s", "--kde=20"]);
+}
+
+#[test]
+fn json_bundle() {
+    let (latency, baseline) = (data("latency.csv"), data("baseline.csv"));
+    check_run(
+        "bundle.json",
+        &[
+            "-c",
+            "1",
+            "-s",
+            "-n",
+            "4",
+            "--no-metadata",
+            "--format-out",
+            "json",
+            "--group-by-file",
+            &latency,
+            &baseline,
+        ],
+    );
+}
+
+#[test]
+fn group_by() {
+    check(
+        "group_by.txt",
+        &[&HISTOGRAM[..], &["--group-by", "0"]].concat(),
+    );
+}